@@ -0,0 +1,134 @@
+//! Compares the longest common substring implementations on the paths in the
+//! fixtures, checking they all find the same common substrings as the full
+//! N*M table alt used to compute, and timing each of them.
+//!
+//! Run with `cargo bench --bench lcs`.
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/alt/path/scoring/lcs/mod.rs"]
+mod lcs;
+
+use lcs::CommonSubstring;
+
+const FIXTURE: &str = "spec/fixtures/ruby_on_rails_discourse_possibles.txt";
+
+const QUERIES: [&str; 3] = [
+    "app/controllers/admin/groups_controller.rb",
+    "spec/controllers/admin/groups_controller_spec.rb",
+    "plugins/discourse-local-dates/assets/javascripts/lib/discourse-markdown/discourse-local-dates.js",
+];
+
+fn full_table(s1: &[char], s2: &[char]) -> CommonSubstring {
+    if s1.is_empty() || s2.is_empty() {
+        return (0, 0, 0);
+    }
+
+    let mut m: Vec<Vec<i32>> = Vec::with_capacity(s1.len());
+    for _ in 0..s1.len() {
+        m.push(vec![0; s2.len()]);
+    }
+
+    let mut longest_length = 0;
+    let mut longest_end = (0, 0);
+    for i in 0..s1.len() {
+        for j in 0..s2.len() {
+            if s1[i] == s2[j] {
+                m[i][j] = 1;
+                if i > 0 && j > 0 {
+                    m[i][j] += m[i - 1][j - 1];
+                }
+                if m[i][j] > longest_length {
+                    longest_length = m[i][j];
+                    longest_end = (i, j);
+                }
+            }
+        }
+    }
+
+    if longest_length == 0 {
+        return (0, 0, 0);
+    }
+    let offset = longest_length as usize - 1;
+    (
+        longest_length as usize,
+        longest_end.0 - offset,
+        longest_end.1 - offset,
+    )
+}
+
+fn time<F: FnMut() -> Vec<CommonSubstring>>(mut f: F) -> (Duration, Vec<CommonSubstring>) {
+    // warm up, then take the best of a few runs
+    let results = f();
+    let mut best = Duration::MAX;
+    for _ in 0..5 {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    (best, results)
+}
+
+fn run(name: &str, pairs: &[(Vec<char>, Vec<char>)]) {
+    let (full, expected) = time(|| pairs.iter().map(|(a, b)| full_table(a, b)).collect());
+    let (rolling, rolling_results) =
+        time(|| pairs.iter().map(|(a, b)| lcs::rolling_row(a, b)).collect());
+    let (automaton, automaton_results) = time(|| {
+        pairs
+            .iter()
+            .map(|(a, b)| lcs::suffix_automaton(a, b))
+            .collect()
+    });
+    let (chosen, chosen_results) = time(|| {
+        pairs
+            .iter()
+            .map(|(a, b)| lcs::longest_common_substring(a, b))
+            .collect()
+    });
+
+    assert_eq!(
+        rolling_results, expected,
+        "rolling row differs for {}",
+        name
+    );
+    assert_eq!(
+        automaton_results, expected,
+        "suffix automaton differs for {}",
+        name
+    );
+    assert_eq!(chosen_results, expected, "chosen differs for {}", name);
+
+    println!("{} ({} pairs, identical results)", name, pairs.len());
+    println!("  full table        {:>12?}", full);
+    println!("  rolling row       {:>12?}", rolling);
+    println!("  suffix automaton  {:>12?}", automaton);
+    println!("  chosen by length  {:>12?}", chosen);
+}
+
+fn main() {
+    let contents = fs::read_to_string(FIXTURE).expect("Failed to read fixture");
+    let paths: Vec<&str> = contents.lines().collect();
+
+    for query in QUERIES.iter() {
+        let query_chars: Vec<char> = query.chars().collect();
+        let pairs: Vec<(Vec<char>, Vec<char>)> = paths
+            .iter()
+            .map(|path| (query_chars.clone(), path.chars().collect()))
+            .collect();
+        run(query, &pairs);
+    }
+
+    // long parent paths, where the suffix automaton is used
+    let long_pairs: Vec<(Vec<char>, Vec<char>)> = paths
+        .windows(2)
+        .map(|pair| {
+            (
+                pair[0].repeat(4).chars().collect(),
+                pair[1].repeat(4).chars().collect(),
+            )
+        })
+        .collect();
+    run("long paths", &long_pairs);
+}
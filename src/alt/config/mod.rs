@@ -0,0 +1,964 @@
+use alt::path::scoring::{SimilarityScorer, TextUnit};
+use alt::path::utils::cleanse_path;
+use alt::profiles::{detect_profiles, find_profile};
+use alt::rules::{Rule, RuleError};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::{de, Deserialize, Deserializer};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const PROJECT_CONFIG_FILENAME: &str = ".alt.toml";
+
+/// A single layer of configuration, e.g. the contents of a config file or the
+/// options given on the command line. Every value is optional so that each
+/// layer only has to specify what it wants to override.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartialConfig {
+    pub truncate: Option<usize>,
+    pub include_hidden: Option<bool>,
+    #[serde(deserialize_with = "deserialize_threads")]
+    pub threads: Option<usize>,
+    pub opposite: Option<bool>,
+    pub graphemes: Option<bool>,
+    pub cache: Option<bool>,
+    pub source: Option<CandidateSource>,
+    pub ignore: Vec<String>,
+    pub weights: PartialWeights,
+    pub rules: Vec<RuleConfig>,
+    pub profiles: Option<Vec<String>>,
+}
+
+/// Where the possible alternates come from: walking the working tree, or the
+/// files git tracks, optionally along with the untracked files it does not
+/// ignore.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CandidateSource {
+    #[default]
+    Walk,
+    Git,
+    GitUntracked,
+}
+
+impl FromStr for CandidateSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "walk" => Ok(CandidateSource::Walk),
+            "git" => Ok(CandidateSource::Git),
+            "git-untracked" => Ok(CandidateSource::GitUntracked),
+            _ => Err(format!(
+                "Unknown source {}, expected walk, git or git-untracked",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CandidateSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CandidateSource::Walk => write!(f, "walk"),
+            CandidateSource::Git => write!(f, "git"),
+            CandidateSource::GitUntracked => write!(f, "git-untracked"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartialWeights {
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub leading_edge_filename: Option<f32>,
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub filename: Option<f32>,
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub path: Option<f32>,
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub token_filename: Option<f32>,
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub unordered_token_filename: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub pattern: String,
+    pub template: String,
+}
+
+// `threads` used to be a flag, so true still means one thread per CPU and
+// false a single thread.
+fn deserialize_threads<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<usize>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Threads {
+        Flag(bool),
+        Count(usize),
+    }
+
+    Ok(
+        Option::<Threads>::deserialize(deserializer)?.map(|threads| match threads {
+            Threads::Flag(true) => 0,
+            Threads::Flag(false) => 1,
+            Threads::Count(threads) => threads,
+        }),
+    )
+}
+
+// A weight of nan or inf would make every score nan or inf, leaving the
+// alternates unranked, and a negative one would rank the least similar first.
+fn deserialize_weight<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    match Option::<f32>::deserialize(deserializer)? {
+        Some(weight) if !weight.is_finite() || weight < 0.0 => Err(de::Error::custom(format!(
+            "invalid weight {}, expected a finite number of at least 0",
+            weight
+        ))),
+        weight => Ok(weight),
+    }
+}
+
+impl PartialConfig {
+    pub fn parse(contents: &str) -> Result<PartialConfig, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+/// Where an effective configuration value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    User(PathBuf),
+    Project(PathBuf),
+    CommandLine,
+    Detected,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::User(path) | Source::Project(path) => write!(f, "{}", path.display()),
+            Source::CommandLine => write!(f, "command line"),
+            Source::Detected => write!(f, "detected from marker files"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sourced<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Sourced<T> {
+    fn default(value: T) -> Self {
+        Sourced {
+            value,
+            source: Source::Default,
+        }
+    }
+
+    fn override_with(&mut self, value: Option<T>, source: &Source) {
+        if let Some(value) = value {
+            self.value = value;
+            self.source = source.clone();
+        }
+    }
+}
+
+/// The effective configuration after merging the defaults, the user config,
+/// the project config and the command line options, in that order of
+/// precedence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub truncate: Sourced<usize>,
+    pub include_hidden: Sourced<bool>,
+    pub threads: Sourced<usize>,
+    pub opposite: Sourced<bool>,
+    pub graphemes: Sourced<bool>,
+    pub cache: Sourced<bool>,
+    pub source: Sourced<CandidateSource>,
+    pub ignore: Vec<Sourced<String>>,
+    pub leading_edge_filename_weight: Sourced<f32>,
+    pub filename_weight: Sourced<f32>,
+    pub path_weight: Sourced<f32>,
+    pub token_filename_weight: Sourced<f32>,
+    pub unordered_token_filename_weight: Sourced<f32>,
+    pub rules: Vec<Sourced<RuleConfig>>,
+    pub profiles: Sourced<Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            truncate: Sourced::default(0),
+            include_hidden: Sourced::default(false),
+            threads: Sourced::default(1),
+            opposite: Sourced::default(false),
+            graphemes: Sourced::default(false),
+            cache: Sourced::default(true),
+            source: Sourced::default(CandidateSource::Walk),
+            ignore: vec![],
+            leading_edge_filename_weight: Sourced::default(100.0),
+            filename_weight: Sourced::default(10.0),
+            path_weight: Sourced::default(1.0),
+            token_filename_weight: Sourced::default(0.0),
+            unordered_token_filename_weight: Sourced::default(0.0),
+            rules: vec![],
+            profiles: Sourced::default(vec![]),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    InvalidIgnorePattern(String, ignore::Error),
+    InvalidRule(Source, RuleError),
+    UnknownProfile(Source, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => {
+                write!(
+                    f,
+                    "Failure occurred reading config {}, {}",
+                    path.display(),
+                    e
+                )
+            }
+            ConfigError::Parse(path, e) => {
+                write!(
+                    f,
+                    "Failure occurred parsing config {}, {}",
+                    path.display(),
+                    e
+                )
+            }
+            ConfigError::InvalidIgnorePattern(pattern, e) => {
+                write!(f, "Invalid ignore pattern {}, {}", pattern, e)
+            }
+            ConfigError::InvalidRule(source, e) => write!(f, "{} in {}", e, source),
+            ConfigError::UnknownProfile(source, name) => {
+                write!(f, "Unknown profile {} in {}", name, source)
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Load the effective configuration for the given working directory,
+    /// layering the user config, the nearest project config and finally the
+    /// given command line overrides on top of the defaults. When no layer
+    /// selects profiles they are detected from the marker files in `cwd`.
+    pub fn load(cwd: &Path, command_line: PartialConfig) -> Result<Config, ConfigError> {
+        Config::load_from(cwd, user_config_path().as_deref(), command_line)
+    }
+
+    /// Load the effective configuration like `load` does, but with the user
+    /// config read from `user_config`, if any, rather than from where it
+    /// lives for the user.
+    pub fn load_from(
+        cwd: &Path,
+        user_config: Option<&Path>,
+        command_line: PartialConfig,
+    ) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+
+        if let Some(path) = user_config.filter(|path| path.is_file()) {
+            let partial = read_partial_config(path)?;
+            config.merge(partial, Source::User(path.to_path_buf()));
+        }
+
+        if let Some(path) = find_project_config(cwd) {
+            let partial = read_partial_config(&path)?;
+            config.merge(partial, Source::Project(path));
+        }
+
+        config.merge(command_line, Source::CommandLine);
+
+        if config.profiles.source == Source::Default {
+            config.profiles = Sourced {
+                value: detect_profiles(cwd)
+                    .iter()
+                    .map(|profile| profile.name().to_string())
+                    .collect(),
+                source: Source::Detected,
+            };
+        }
+
+        Ok(config)
+    }
+
+    pub fn merge(&mut self, partial: PartialConfig, source: Source) {
+        self.truncate.override_with(partial.truncate, &source);
+        self.include_hidden
+            .override_with(partial.include_hidden, &source);
+        self.threads.override_with(partial.threads, &source);
+        self.opposite.override_with(partial.opposite, &source);
+        self.graphemes.override_with(partial.graphemes, &source);
+        self.cache.override_with(partial.cache, &source);
+        self.source.override_with(partial.source, &source);
+        self.leading_edge_filename_weight
+            .override_with(partial.weights.leading_edge_filename, &source);
+        self.filename_weight
+            .override_with(partial.weights.filename, &source);
+        self.path_weight
+            .override_with(partial.weights.path, &source);
+        self.token_filename_weight
+            .override_with(partial.weights.token_filename, &source);
+        self.unordered_token_filename_weight
+            .override_with(partial.weights.unordered_token_filename, &source);
+        self.profiles.override_with(partial.profiles, &source);
+        self.ignore
+            .extend(partial.ignore.into_iter().map(|pattern| Sourced {
+                value: pattern,
+                source: source.clone(),
+            }));
+
+        // rules from higher precedence layers are evaluated first
+        let mut rules: Vec<Sourced<RuleConfig>> = partial
+            .rules
+            .into_iter()
+            .map(|rule| Sourced {
+                value: rule,
+                source: source.clone(),
+            })
+            .collect();
+        rules.append(&mut self.rules);
+        self.rules = rules;
+    }
+
+    /// The scorer with the configured weights and text unit.
+    pub fn scorer(&self) -> SimilarityScorer {
+        SimilarityScorer::new(
+            self.leading_edge_filename_weight.value,
+            self.filename_weight.value,
+            self.path_weight.value,
+        )
+        .with_token_filename_weights(
+            self.token_filename_weight.value,
+            self.unordered_token_filename_weight.value,
+        )
+        .with_text_unit(if self.graphemes.value {
+            TextUnit::Grapheme
+        } else {
+            TextUnit::Char
+        })
+    }
+
+    /// Compile the configured rules followed by the rules of the selected
+    /// profiles.
+    pub fn compile_rules(&self) -> Result<Vec<Rule>, ConfigError> {
+        let mut rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                Rule::new(&rule.value.pattern, &rule.value.template)
+                    .map_err(|e| ConfigError::InvalidRule(rule.source.clone(), e))
+            })
+            .collect::<Result<Vec<Rule>, ConfigError>>()?;
+
+        for name in self.profiles.value.iter() {
+            let profile = find_profile(name).ok_or_else(|| {
+                ConfigError::UnknownProfile(self.profiles.source.clone(), name.clone())
+            })?;
+            rules.extend(profile.compile_rules());
+        }
+
+        Ok(rules)
+    }
+
+    /// Build a matcher for the configured ignore patterns. Patterns use
+    /// gitignore syntax and those from a project config are relative to the
+    /// directory containing it, all others are relative to `cwd`.
+    pub fn ignore_matcher(&self, cwd: &Path) -> Result<IgnoreMatcher, ConfigError> {
+        let mut roots: Vec<(PathBuf, GitignoreBuilder)> = vec![];
+
+        for pattern in self.ignore.iter() {
+            let root = match &pattern.source {
+                Source::Project(path) => path.parent().unwrap_or(cwd).to_path_buf(),
+                _ => cwd.to_path_buf(),
+            };
+            let index = match roots.iter().position(|(r, _)| *r == root) {
+                Some(index) => index,
+                None => {
+                    let builder = GitignoreBuilder::new(&root);
+                    roots.push((root, builder));
+                    roots.len() - 1
+                }
+            };
+            roots[index]
+                .1
+                .add_line(None, &pattern.value)
+                .map_err(|e| ConfigError::InvalidIgnorePattern(pattern.value.clone(), e))?;
+        }
+
+        let mut gitignores = vec![];
+        for (root, builder) in roots.into_iter() {
+            let gitignore = builder
+                .build()
+                .map_err(|e| ConfigError::InvalidIgnorePattern(root.display().to_string(), e))?;
+            gitignores.push((root, gitignore));
+        }
+
+        Ok(IgnoreMatcher {
+            cwd: cwd.to_path_buf(),
+            gitignores,
+        })
+    }
+}
+
+/// Matches candidate paths, relative to the working directory, against the
+/// configured ignore patterns.
+pub struct IgnoreMatcher {
+    cwd: PathBuf,
+    gitignores: Vec<(PathBuf, Gitignore)>,
+}
+
+impl IgnoreMatcher {
+    pub fn is_ignored<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = self.cwd.join(cleanse_path(path.as_ref()));
+        self.gitignores.iter().any(|(root, gitignore)| {
+            path.starts_with(root)
+                && gitignore
+                    .matched_path_or_any_parents(&path, false)
+                    .is_ignore()
+        })
+    }
+}
+
+fn read_partial_config(path: &Path) -> Result<PartialConfig, ConfigError> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+    PartialConfig::parse(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+}
+
+/// The user level config lives at `$XDG_CONFIG_HOME/alt/config.toml`, falling
+/// back to `~/.config/alt/config.toml`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("alt").join("config.toml"))
+}
+
+/// Find the nearest project config by walking from `start` up through its
+/// ancestors, stopping at the root of the repository (the first directory
+/// containing `.git`).
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let candidate = dir.join(PROJECT_CONFIG_FILENAME);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if dir.join(".git").exists() {
+            return None;
+        }
+    }
+    None
+}
+
+/// Render the configuration as TOML, annotating each value with where it
+/// came from.
+pub fn config_to_string(config: &Config) -> String {
+    let mut lines: Vec<String> = vec![
+        format!(
+            "truncate = {} # {}",
+            config.truncate.value, config.truncate.source
+        ),
+        format!(
+            "include_hidden = {} # {}",
+            config.include_hidden.value, config.include_hidden.source
+        ),
+        format!(
+            "threads = {} # {}",
+            config.threads.value, config.threads.source
+        ),
+        format!(
+            "opposite = {} # {}",
+            config.opposite.value, config.opposite.source
+        ),
+        format!(
+            "graphemes = {} # {}",
+            config.graphemes.value, config.graphemes.source
+        ),
+        format!("cache = {} # {}", config.cache.value, config.cache.source),
+        format!(
+            "source = \"{}\" # {}",
+            config.source.value, config.source.source
+        ),
+        format!(
+            "profiles = {:?} # {}",
+            config.profiles.value, config.profiles.source
+        ),
+    ];
+
+    if config.ignore.is_empty() {
+        lines.push("ignore = [] # default".to_string());
+    } else {
+        lines.push("ignore = [".to_string());
+        for pattern in config.ignore.iter() {
+            lines.push(format!("    {:?}, # {}", pattern.value, pattern.source));
+        }
+        lines.push("]".to_string());
+    }
+
+    lines.push(String::new());
+    lines.push("[weights]".to_string());
+    lines.push(format!(
+        "leading_edge_filename = {:?} # {}",
+        config.leading_edge_filename_weight.value, config.leading_edge_filename_weight.source
+    ));
+    lines.push(format!(
+        "filename = {:?} # {}",
+        config.filename_weight.value, config.filename_weight.source
+    ));
+    lines.push(format!(
+        "path = {:?} # {}",
+        config.path_weight.value, config.path_weight.source
+    ));
+    lines.push(format!(
+        "token_filename = {:?} # {}",
+        config.token_filename_weight.value, config.token_filename_weight.source
+    ));
+    lines.push(format!(
+        "unordered_token_filename = {:?} # {}",
+        config.unordered_token_filename_weight.value, config.unordered_token_filename_weight.source
+    ));
+
+    for rule in config.rules.iter() {
+        lines.push(String::new());
+        lines.push(format!("[[rules]] # {}", rule.source));
+        lines.push(format!("pattern = {:?}", rule.value.pattern));
+        lines.push(format!("template = {:?}", rule.value.template));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{
+        config_to_string, find_project_config, CandidateSource, Config, ConfigError, PartialConfig,
+        PartialWeights, RuleConfig, Source, Sourced,
+    };
+    use serde_json;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn partial_config_parse_reads_all_values() {
+        let partial = PartialConfig::parse(
+            r#"
+            truncate = 3
+            include_hidden = true
+            threads = 4
+            opposite = true
+            graphemes = true
+            cache = false
+            source = "git-untracked"
+            ignore = ["vendor/", "*.min.js"]
+            profiles = ["rails"]
+
+            [weights]
+            leading_edge_filename = 50.0
+            filename = 5.0
+            path = 2.0
+            token_filename = 3.0
+            unordered_token_filename = 4.0
+
+            [[rules]]
+            pattern = "spec/**/*_spec.rb"
+            template = "app/{1}/{2}.rb"
+            "#,
+        )
+        .expect("Failed to parse config");
+
+        assert_eq!(
+            partial,
+            PartialConfig {
+                truncate: Some(3),
+                include_hidden: Some(true),
+                threads: Some(4),
+                opposite: Some(true),
+                graphemes: Some(true),
+                cache: Some(false),
+                source: Some(CandidateSource::GitUntracked),
+                ignore: vec!["vendor/".to_string(), "*.min.js".to_string()],
+                weights: PartialWeights {
+                    leading_edge_filename: Some(50.0),
+                    filename: Some(5.0),
+                    path: Some(2.0),
+                    token_filename: Some(3.0),
+                    unordered_token_filename: Some(4.0),
+                },
+                rules: vec![RuleConfig {
+                    pattern: "spec/**/*_spec.rb".to_string(),
+                    template: "app/{1}/{2}.rb".to_string(),
+                }],
+                profiles: Some(vec!["rails".to_string()]),
+            }
+        );
+    }
+
+    #[test]
+    fn partial_config_parse_allows_missing_values() {
+        let partial = PartialConfig::parse("truncate = 1").expect("Failed to parse config");
+        assert_eq!(partial.truncate, Some(1));
+        assert_eq!(partial.weights, PartialWeights::default());
+    }
+
+    #[test]
+    fn partial_config_parse_accepts_threads_as_a_flag() {
+        let parse_threads = |contents| PartialConfig::parse(contents).unwrap().threads;
+        assert_eq!(parse_threads("threads = true"), Some(0));
+        assert_eq!(parse_threads("threads = false"), Some(1));
+        assert_eq!(parse_threads("threads = 8"), Some(8));
+        assert_eq!(parse_threads("truncate = 1"), None);
+        assert!(PartialConfig::parse("threads = -1").is_err());
+    }
+
+    #[test]
+    fn partial_config_round_trips_through_json() {
+        let partial = PartialConfig::parse(
+            "threads = 4\nignore = [\"vendor/\"]\n[weights]\npath = 2.0\n[[rules]]\npattern = \"*.rb\"\ntemplate = \"{1}_spec.rb\"",
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&partial).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PartialConfig>(&json).unwrap(),
+            partial
+        );
+        let json = serde_json::to_string(&PartialConfig::default()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PartialConfig>(&json).unwrap(),
+            PartialConfig::default()
+        );
+    }
+
+    #[test]
+    fn candidate_source_parses_its_names() {
+        assert_eq!("walk".parse(), Ok(CandidateSource::Walk));
+        assert_eq!("git".parse(), Ok(CandidateSource::Git));
+        assert_eq!("git-untracked".parse(), Ok(CandidateSource::GitUntracked));
+        assert!("svn".parse::<CandidateSource>().is_err());
+        assert!(PartialConfig::parse("source = \"svn\"").is_err());
+    }
+
+    #[test]
+    fn partial_config_parse_rejects_unknown_keys() {
+        assert!(PartialConfig::parse("truncat = 1").is_err());
+    }
+
+    #[test]
+    fn partial_config_parse_rejects_weights_that_are_not_finite_or_are_negative() {
+        for weight in ["nan", "inf", "-inf", "-1.0"] {
+            let contents = format!("[weights]\nleading_edge_filename = {}", weight);
+            assert!(PartialConfig::parse(&contents).is_err(), "{}", weight);
+        }
+        assert!(PartialConfig::parse("[weights]\npath = 0.0").is_ok());
+    }
+
+    #[test]
+    fn config_load_from_fails_on_a_user_config_with_an_invalid_weight() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        let user_config = dir.path().join("config.toml");
+        fs::write(&user_config, "[weights]\nfilename = nan").unwrap();
+
+        match Config::load_from(dir.path(), Some(&user_config), PartialConfig::default()) {
+            Err(ConfigError::Parse(path, e)) => {
+                assert_eq!(path, user_config);
+                assert!(e.to_string().contains("invalid weight NaN"), "{}", e);
+            }
+            result => panic!("Expected the user config to be rejected, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn config_merge_overrides_in_order_of_precedence() {
+        let mut config = Config::default();
+        let user = PathBuf::from("/home/me/.config/alt/config.toml");
+        let project = PathBuf::from("/repo/.alt.toml");
+
+        config.merge(
+            PartialConfig::parse("truncate = 5\n[weights]\npath = 2.0").unwrap(),
+            Source::User(user.clone()),
+        );
+        config.merge(
+            PartialConfig::parse("truncate = 1").unwrap(),
+            Source::Project(project.clone()),
+        );
+        config.merge(
+            PartialConfig {
+                include_hidden: Some(true),
+                ..PartialConfig::default()
+            },
+            Source::CommandLine,
+        );
+
+        assert_eq!(
+            config.truncate,
+            Sourced {
+                value: 1,
+                source: Source::Project(project)
+            }
+        );
+        assert_eq!(
+            config.path_weight,
+            Sourced {
+                value: 2.0,
+                source: Source::User(user)
+            }
+        );
+        assert_eq!(
+            config.include_hidden,
+            Sourced {
+                value: true,
+                source: Source::CommandLine
+            }
+        );
+        assert_eq!(config.filename_weight.source, Source::Default);
+    }
+
+    #[test]
+    fn config_merge_accumulates_ignore_patterns() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("ignore = [\"a/\"]").unwrap(),
+            Source::User(PathBuf::from("/u")),
+        );
+        config.merge(
+            PartialConfig::parse("ignore = [\"b/\"]").unwrap(),
+            Source::Project(PathBuf::from("/p/.alt.toml")),
+        );
+
+        let patterns: Vec<&str> = config.ignore.iter().map(|p| p.value.as_str()).collect();
+        assert_eq!(patterns, vec!["a/", "b/"]);
+    }
+
+    #[test]
+    fn config_merge_orders_rules_from_higher_precedence_first() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("[[rules]]\npattern = \"a/*\"\ntemplate = \"b/{1}\"").unwrap(),
+            Source::User(PathBuf::from("/u")),
+        );
+        config.merge(
+            PartialConfig::parse("[[rules]]\npattern = \"c/*\"\ntemplate = \"d/{1}\"").unwrap(),
+            Source::Project(PathBuf::from("/p/.alt.toml")),
+        );
+
+        let patterns: Vec<&str> = config
+            .rules
+            .iter()
+            .map(|rule| rule.value.pattern.as_str())
+            .collect();
+        assert_eq!(patterns, vec!["c/*", "a/*"]);
+    }
+
+    #[test]
+    fn compile_rules_reports_the_source_of_invalid_rules() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("[[rules]]\npattern = \"a/*\"\ntemplate = \"b/{2}\"").unwrap(),
+            Source::Project(PathBuf::from("/p/.alt.toml")),
+        );
+
+        match config.compile_rules() {
+            Err(ConfigError::InvalidRule(source, _)) => {
+                assert_eq!(source, Source::Project(PathBuf::from("/p/.alt.toml")))
+            }
+            _ => panic!("expected an invalid rule error"),
+        }
+    }
+
+    #[test]
+    fn compile_rules_appends_profile_rules_after_configured_rules() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse(
+                "profiles = [\"go\"]\n[[rules]]\npattern = \"a/*\"\ntemplate = \"b/{1}\"",
+            )
+            .unwrap(),
+            Source::Project(PathBuf::from("/p/.alt.toml")),
+        );
+
+        let rules = config.compile_rules().unwrap();
+        assert_eq!(rules[0].pattern(), "a/*");
+        assert_eq!(rules[1].pattern(), "**/*_test.go");
+    }
+
+    #[test]
+    fn compile_rules_reports_unknown_profiles() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("profiles = [\"cobol\"]").unwrap(),
+            Source::CommandLine,
+        );
+
+        match config.compile_rules() {
+            Err(ConfigError::UnknownProfile(source, name)) => {
+                assert_eq!(source, Source::CommandLine);
+                assert_eq!(name, "cobol");
+            }
+            _ => panic!("expected an unknown profile error"),
+        }
+    }
+
+    #[test]
+    fn config_load_detects_profiles_when_none_are_selected() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("go.mod"), "").unwrap();
+
+        let config = Config::load_from(dir.path(), None, PartialConfig::default()).unwrap();
+        assert_eq!(
+            config.profiles,
+            Sourced {
+                value: vec!["go".to_string()],
+                source: Source::Detected
+            }
+        );
+    }
+
+    #[test]
+    fn config_load_does_not_detect_profiles_when_they_are_selected() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("go.mod"), "").unwrap();
+
+        let config = Config::load_from(
+            dir.path(),
+            None,
+            PartialConfig {
+                profiles: Some(vec![]),
+                ..PartialConfig::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            config.profiles,
+            Sourced {
+                value: vec![],
+                source: Source::CommandLine
+            }
+        );
+    }
+
+    #[test]
+    fn ignore_matcher_matches_paths_and_their_parents() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("ignore = [\"vendor/\", \"*.min.js\"]").unwrap(),
+            Source::CommandLine,
+        );
+        let matcher = config.ignore_matcher(&PathBuf::from("/repo")).unwrap();
+
+        assert!(matcher.is_ignored("vendor/foo/bar.rb"));
+        assert!(matcher.is_ignored("./vendor/foo/bar.rb"));
+        assert!(matcher.is_ignored("app/assets/app.min.js"));
+        assert!(!matcher.is_ignored("app/models/vendor.rb"));
+    }
+
+    #[test]
+    fn ignore_matcher_anchors_project_patterns_to_the_project_root() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("ignore = [\"/app/legacy\"]").unwrap(),
+            Source::Project(PathBuf::from("/repo/.alt.toml")),
+        );
+        let matcher = config.ignore_matcher(&PathBuf::from("/repo/app")).unwrap();
+
+        assert!(matcher.is_ignored("legacy/user.rb"));
+        assert!(!matcher.is_ignored("models/user.rb"));
+    }
+
+    #[test]
+    fn find_project_config_finds_nearest_config_in_ancestors() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("app/models");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join(".alt.toml"), "truncate = 1").unwrap();
+
+        assert_eq!(
+            find_project_config(&nested),
+            Some(dir.path().join(".alt.toml"))
+        );
+    }
+
+    #[test]
+    fn find_project_config_stops_at_the_repository_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(dir.path().join(".alt.toml"), "truncate = 1").unwrap();
+
+        assert_eq!(find_project_config(&repo), None);
+    }
+
+    #[test]
+    fn config_to_string_annotates_values_with_their_source() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("truncate = 1\nignore = [\"vendor/\"]").unwrap(),
+            Source::Project(PathBuf::from("/repo/.alt.toml")),
+        );
+
+        assert_eq!(
+            config_to_string(&config),
+            "truncate = 1 # /repo/.alt.toml
+include_hidden = false # default
+threads = 1 # default
+opposite = false # default
+graphemes = false # default
+cache = true # default
+source = \"walk\" # default
+profiles = [] # default
+ignore = [
+    \"vendor/\", # /repo/.alt.toml
+]
+
+[weights]
+leading_edge_filename = 100.0 # default
+filename = 10.0 # default
+path = 1.0 # default
+token_filename = 0.0 # default
+unordered_token_filename = 0.0 # default"
+        );
+    }
+
+    #[test]
+    fn config_to_string_includes_rules() {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig::parse("[[rules]]\npattern = \"a/*\"\ntemplate = \"b/{1}\"").unwrap(),
+            Source::Project(PathBuf::from("/repo/.alt.toml")),
+        );
+
+        assert!(config_to_string(&config).ends_with(
+            "unordered_token_filename = 0.0 # default
+
+[[rules]] # /repo/.alt.toml
+pattern = \"a/*\"
+template = \"b/{1}\""
+        ));
+    }
+}
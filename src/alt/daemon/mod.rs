@@ -0,0 +1,300 @@
+use alt::config::PartialConfig;
+use alt::path::scoring::ScoredPath;
+use alt::path::utils::{fnv1a, path_to_bytes, SerializedPath};
+use serde_json;
+use std::env;
+use std::fs;
+use std::fs::DirBuilder;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+// How long a connected client has to send its next request before the
+// daemon closes its connection.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How long a client waits on a busy or stuck daemon, which serves one client
+// at a time, before giving up on it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A request sent to the daemon, one JSON document per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    /// Rank the alternates of the cleansed `path`, with the configuration
+    /// of the daemon's directory overridden by `overrides`, e.g. the
+    /// options given on the command line.
+    Alternates {
+        path: SerializedPath,
+        overrides: PartialConfig,
+    },
+    /// Stop the daemon.
+    Stop,
+}
+
+/// The daemon's response to a request, one JSON document per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The ranked alternates, best first, along with their scores.
+    Alternates(Vec<(f32, SerializedPath)>),
+    /// The request failed, e.g. because of an invalid config file.
+    Error(String),
+    Stopped,
+}
+
+impl Response {
+    pub fn alternates(scored_paths: &[ScoredPath]) -> Self {
+        Response::Alternates(
+            scored_paths
+                .iter()
+                .map(|(score, path)| (*score, SerializedPath::new(path)))
+                .collect(),
+        )
+    }
+}
+
+/// Where the daemon for the given directory listens. Each directory gets a
+/// daemon of its own, as alternates are found relative to the directory alt
+/// is run in.
+pub fn socket_path(cwd: &Path) -> PathBuf {
+    let dir = match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join("alt"),
+        None => match env::var("USER") {
+            Ok(user) if !user.is_empty() => env::temp_dir().join(format!("alt-{}", user)),
+            _ => env::temp_dir().join("alt"),
+        },
+    };
+    dir.join(format!("{:016x}.sock", fnv1a(&[&path_to_bytes(cwd)])))
+}
+
+/// Listen on the socket, replacing one left behind by a daemon that did not
+/// stop cleanly, unless a daemon is still listening on it.
+pub fn bind(socket_path: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = socket_path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        check_private(dir)?;
+    }
+    if UnixStream::connect(socket_path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "a daemon is already listening on it",
+        ));
+    }
+    match fs::remove_file(socket_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    UnixListener::bind(socket_path)
+}
+
+// Other users must not be able to listen in place of the daemon, so the
+// directory of the socket has to be accessible only by its owner.
+fn check_private(dir: &Path) -> io::Result<()> {
+    if fs::metadata(dir)?.permissions().mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is accessible by other users", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Answer requests until one asks the daemon to stop, handling each
+/// connection on a thread of its own, so that a client staying connected
+/// does not hold up the others. Alternates are ranked by `alternates`, for
+/// one request at a time, given the cleansed query path and the overrides of
+/// the request.
+pub fn serve<F>(listener: &UnixListener, alternates: F) -> io::Result<()>
+where
+    F: FnMut(&Path, PartialConfig) -> Result<Vec<ScoredPath>, String> + Send,
+{
+    let alternates = &Mutex::new(alternates);
+    let stopping = &AtomicBool::new(false);
+    let socket_path = &listener.local_addr()?.as_pathname().map(Path::to_path_buf);
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            if stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            // a client that went away or misbehaved does not stop the daemon
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            scope.spawn(move || {
+                if let Ok(true) = handle_connection(stream, alternates) {
+                    stopping.store(true, Ordering::SeqCst);
+                    // wake the loop up from waiting for the next connection
+                    if let Some(socket_path) = socket_path {
+                        let _ = UnixStream::connect(socket_path);
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+// Answer each request of the connection, returning whether one asked the
+// daemon to stop.
+fn handle_connection<F>(stream: UnixStream, alternates: &Mutex<F>) -> io::Result<bool>
+where
+    F: FnMut(&Path, PartialConfig) -> Result<Vec<ScoredPath>, String>,
+{
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let (response, stop) = match serde_json::from_str(&line?) {
+            Ok(Request::Alternates { path, overrides }) => {
+                let mut alternates = alternates.lock().unwrap_or_else(PoisonError::into_inner);
+                match alternates(&path.to_path_buf(), overrides) {
+                    Ok(scored_paths) => (Response::alternates(&scored_paths), false),
+                    Err(e) => (Response::Error(e), false),
+                }
+            }
+            Ok(Request::Stop) => (Response::Stopped, true),
+            Err(e) => (Response::Error(format!("Invalid request, {}", e)), false),
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+        if stop {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Send a request to the daemon listening on the socket and wait for its
+/// response, failing when the daemon takes longer than `REQUEST_TIMEOUT` to
+/// take or answer it.
+pub fn request(socket_path: &Path, request: &Request) -> io::Result<Response> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    serde_json::to_writer(&mut stream, request)?;
+    stream.write_all(b"\n")?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{bind, request, serve, socket_path, Request, Response, REQUEST_TIMEOUT};
+    use alt::config::PartialConfig;
+    use alt::path::utils::SerializedPath;
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn socket_path_differs_by_directory() {
+        assert_ne!(
+            socket_path(Path::new("/repo")),
+            socket_path(Path::new("/repo/app"))
+        );
+    }
+
+    #[test]
+    fn daemon_answers_requests_until_asked_to_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("alt/daemon.sock");
+        let listener = bind(&socket).unwrap();
+        let daemon = thread::spawn(move || {
+            serve(&listener, |path, overrides| match overrides.truncate {
+                Some(0) => Err("Invalid truncate".to_string()),
+                _ => Ok(vec![(0.5, path.with_extension("spec.rb"))]),
+            })
+        });
+
+        let alternates = |truncate: usize| Request::Alternates {
+            path: SerializedPath::new(Path::new("app/foo.rb")),
+            overrides: PartialConfig {
+                truncate: Some(truncate),
+                ..PartialConfig::default()
+            },
+        };
+        assert_eq!(
+            request(&socket, &alternates(1)).unwrap(),
+            Response::Alternates(vec![(
+                0.5,
+                SerializedPath::new(&PathBuf::from("app/foo.spec.rb"))
+            )])
+        );
+        assert_eq!(
+            request(&socket, &alternates(0)).unwrap(),
+            Response::Error("Invalid truncate".to_string())
+        );
+        assert_eq!(request(&socket, &Request::Stop).unwrap(), Response::Stopped);
+        daemon.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn request_gives_up_on_a_daemon_that_does_not_answer() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("alt/daemon.sock");
+        let _listener = bind(&socket).unwrap();
+
+        let started = Instant::now();
+        assert!(request(&socket, &Request::Stop).is_err());
+        assert!(started.elapsed() < REQUEST_TIMEOUT * 2);
+    }
+
+    #[test]
+    fn daemon_answers_a_client_while_another_stays_connected() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("alt/daemon.sock");
+        let listener = bind(&socket).unwrap();
+        let daemon = thread::spawn(move || {
+            serve(&listener, |path, _| {
+                Ok(vec![(0.5, path.with_extension("spec.rb"))])
+            })
+        });
+
+        let idle = UnixStream::connect(&socket).unwrap();
+        let alternates = Request::Alternates {
+            path: SerializedPath::new(Path::new("app/foo.rb")),
+            overrides: PartialConfig::default(),
+        };
+        assert_eq!(
+            request(&socket, &alternates).unwrap(),
+            Response::Alternates(vec![(
+                0.5,
+                SerializedPath::new(&PathBuf::from("app/foo.spec.rb"))
+            )])
+        );
+        assert_eq!(request(&socket, &Request::Stop).unwrap(), Response::Stopped);
+        drop(idle);
+        daemon.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn bind_refuses_a_socket_a_daemon_is_listening_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("alt/daemon.sock");
+
+        let _listener = bind(&socket).unwrap();
+
+        assert!(bind(&socket).is_err());
+    }
+
+    #[test]
+    fn bind_replaces_a_socket_left_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("alt/daemon.sock");
+        drop(bind(&socket).unwrap());
+
+        assert!(bind(&socket).is_ok());
+    }
+}
@@ -0,0 +1,357 @@
+use alt::path::utils::path_from_bytes;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+// The mode git records submodules with, which are directories rather than
+// files.
+const SUBMODULE_MODE: &[u8] = b"160000";
+
+/// Which changes to list the changed files of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Changes {
+    /// The working tree, staged or not, against HEAD.
+    WorkingTree,
+    /// The staged changes against HEAD.
+    Staged,
+    /// The commits since the given revision, `REV..HEAD`.
+    Since(String),
+}
+
+#[derive(Debug)]
+pub enum GitError {
+    Run(io::Error),
+    /// git exited unsuccessfully, with what it wrote to stderr.
+    Failed(String),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Run(e) => write!(f, "Failure occurred running git, {}", e),
+            GitError::Failed(message) => write!(f, "Failure occurred running git, {}", message),
+        }
+    }
+}
+
+/// The files below `cwd` that git tracks, relative to it, optionally along
+/// with the untracked files that are not ignored. Only the local repository is
+/// read, so it works without network access. Tracked files deleted from the
+/// working tree and submodules are left out, as are hidden entries, those with
+/// a component beginning with a dot, unless `include_hidden`, as they are when
+/// walking.
+pub fn ls_files(
+    cwd: &Path,
+    untracked: bool,
+    include_hidden: bool,
+) -> Result<Vec<PathBuf>, GitError> {
+    let deleted: HashSet<Vec<u8>> = git_ls_files(cwd, &["--deleted"])?.into_iter().collect();
+
+    // staged entries are listed as `<mode> <object> <stage>\t<path>`, once
+    // per stage while merging
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    let mut paths: Vec<Vec<u8>> = git_ls_files(cwd, &["--stage"])?
+        .into_iter()
+        .filter_map(|entry| {
+            let tab = entry.iter().position(|&b| b == b'\t')?;
+            match entry.starts_with(SUBMODULE_MODE) {
+                true => None,
+                false => Some(entry[tab + 1..].to_vec()),
+            }
+        })
+        .filter(|path| !deleted.contains(path) && seen.insert(path.clone()))
+        .collect();
+    if untracked {
+        paths.extend(git_ls_files(cwd, &["--others", "--exclude-standard"])?);
+    }
+
+    Ok(paths
+        .into_iter()
+        .map(path_from_bytes)
+        .filter(|path| include_hidden || !is_hidden(path))
+        .collect())
+}
+
+/// The files below `cwd` with the given changes, relative to it, as diffed by
+/// the local repository. Deleted files are listed too, as their alternates
+/// may well be affected, and so are the untracked files that are not ignored
+/// for the working tree. Before the first commit every file is a change.
+pub fn diff_files(cwd: &Path, changes: &Changes) -> Result<Vec<PathBuf>, GitError> {
+    let mut args = vec!["diff", "--name-only", "-z", "--relative"];
+    let head;
+    let range;
+    match changes {
+        Changes::WorkingTree => {
+            head = head_or_empty_tree(cwd)?;
+            args.push(&head);
+        }
+        Changes::Staged => {
+            head = head_or_empty_tree(cwd)?;
+            args.extend(["--cached", &head]);
+        }
+        Changes::Since(rev) => {
+            range = format!("{}..HEAD", rev);
+            // a revision beginning with a dash is not taken as an option
+            args.extend(["--end-of-options", &range]);
+        }
+    }
+    // the revisions end here, so none is taken as a path
+    args.push("--");
+    let mut paths = git(cwd, &args)?;
+    if *changes == Changes::WorkingTree {
+        paths.extend(git_ls_files(cwd, &["--others", "--exclude-standard"])?);
+        paths.sort();
+    }
+    Ok(paths.into_iter().map(path_from_bytes).collect())
+}
+
+// HEAD, or the empty tree while HEAD is yet to be born, as there are no
+// commits yet.
+fn head_or_empty_tree(cwd: &Path) -> Result<String, GitError> {
+    if git(cwd, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() {
+        return Ok("HEAD".to_string());
+    }
+    // hashed rather than hard coded, as it differs by the hash the
+    // repository uses
+    let tree = git(cwd, &["hash-object", "-t", "tree", "--stdin"])?.concat();
+    Ok(String::from_utf8_lossy(&tree).trim().to_string())
+}
+
+fn git_ls_files(cwd: &Path, args: &[&str]) -> Result<Vec<Vec<u8>>, GitError> {
+    let mut ls_files_args = vec!["ls-files", "-z"];
+    ls_files_args.extend(args);
+    git(cwd, &ls_files_args)
+}
+
+// Run git in `cwd`, splitting its NUL terminated entries, which are output
+// byte for byte rather than quoted.
+fn git(cwd: &Path, args: &[&str]) -> Result<Vec<Vec<u8>>, GitError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .map_err(GitError::Run)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(GitError::Failed(stderr.trim().to_string()));
+    }
+    Ok(output
+        .stdout
+        .split(|&b| b == b'\0')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| entry.to_vec())
+        .collect())
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{diff_files, ls_files, Changes, GitError};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .expect("Failed to run git");
+        assert!(status.success());
+    }
+
+    fn repo(files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    fn sorted(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn ls_files_lists_the_tracked_files() {
+        let dir = repo(&["app/user.rb", "spec/user_spec.rb", "tmp/build.log"]);
+        git(dir.path(), &["add", "app", "spec"]);
+
+        assert_eq!(
+            sorted(ls_files(dir.path(), false, false).unwrap()),
+            vec![
+                PathBuf::from("app/user.rb"),
+                PathBuf::from("spec/user_spec.rb")
+            ]
+        );
+    }
+
+    #[test]
+    fn ls_files_adds_the_untracked_files_that_are_not_ignored() {
+        let dir = repo(&["app/user.rb", "lib/new.rb", "tmp/build.log", ".gitignore"]);
+        fs::write(dir.path().join(".gitignore"), "tmp/\n").unwrap();
+        git(dir.path(), &["add", "app"]);
+
+        assert_eq!(
+            sorted(ls_files(dir.path(), true, false).unwrap()),
+            vec![PathBuf::from("app/user.rb"), PathBuf::from("lib/new.rb")]
+        );
+    }
+
+    #[test]
+    fn ls_files_leaves_out_deleted_and_hidden_files() {
+        let dir = repo(&["app/user.rb", "app/gone.rb", ".github/ci.yml"]);
+        git(dir.path(), &["add", "."]);
+        fs::remove_file(dir.path().join("app/gone.rb")).unwrap();
+
+        assert_eq!(
+            sorted(ls_files(dir.path(), false, false).unwrap()),
+            vec![PathBuf::from("app/user.rb")]
+        );
+        assert_eq!(
+            sorted(ls_files(dir.path(), false, true).unwrap()),
+            vec![
+                PathBuf::from(".github/ci.yml"),
+                PathBuf::from("app/user.rb")
+            ]
+        );
+    }
+
+    #[test]
+    fn ls_files_lists_paths_relative_to_a_subdirectory() {
+        let dir = repo(&["app/models/user.rb", "spec/user_spec.rb"]);
+        git(dir.path(), &["add", "."]);
+
+        assert_eq!(
+            ls_files(&dir.path().join("app"), false, false).unwrap(),
+            vec![PathBuf::from("models/user.rb")]
+        );
+    }
+
+    #[test]
+    fn ls_files_outside_a_repository_fails_with_what_git_reported() {
+        let dir = tempfile::tempdir().unwrap();
+
+        match ls_files(dir.path(), false, false) {
+            Err(GitError::Failed(message)) => assert!(message.contains("not a git repository")),
+            result => panic!("Expected git to fail, got {:?}", result),
+        }
+    }
+
+    fn commit(dir: &Path, message: &str) {
+        git(
+            dir,
+            &[
+                "-c",
+                "user.name=alt",
+                "-c",
+                "user.email=alt@example.com",
+                "commit",
+                "-q",
+                "-m",
+                message,
+            ],
+        );
+    }
+
+    #[test]
+    fn diff_files_lists_the_changes_of_the_working_tree_staged_or_since_a_revision() {
+        let dir = repo(&["app/user.rb", "app/account.rb", "lib/tax.rb"]);
+        git(dir.path(), &["add", "."]);
+        commit(dir.path(), "first");
+        git(dir.path(), &["tag", "first"]);
+        fs::write(dir.path().join("lib/tax.rb"), "committed").unwrap();
+        git(dir.path(), &["add", "."]);
+        commit(dir.path(), "second");
+        fs::write(dir.path().join("app/user.rb"), "staged").unwrap();
+        git(dir.path(), &["add", "."]);
+        fs::remove_file(dir.path().join("app/account.rb")).unwrap();
+
+        assert_eq!(
+            diff_files(dir.path(), &Changes::WorkingTree).unwrap(),
+            vec![
+                PathBuf::from("app/account.rb"),
+                PathBuf::from("app/user.rb")
+            ]
+        );
+        assert_eq!(
+            diff_files(dir.path(), &Changes::Staged).unwrap(),
+            vec![PathBuf::from("app/user.rb")]
+        );
+        assert_eq!(
+            diff_files(dir.path(), &Changes::Since("first".to_string())).unwrap(),
+            vec![PathBuf::from("lib/tax.rb")]
+        );
+        assert_eq!(
+            diff_files(&dir.path().join("app"), &Changes::WorkingTree).unwrap(),
+            vec![PathBuf::from("account.rb"), PathBuf::from("user.rb")]
+        );
+    }
+
+    #[test]
+    fn diff_files_lists_the_untracked_files_of_the_working_tree_that_are_not_ignored() {
+        let dir = repo(&["app/user.rb", ".gitignore"]);
+        fs::write(dir.path().join(".gitignore"), "tmp/\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        commit(dir.path(), "first");
+        fs::create_dir_all(dir.path().join("spec")).unwrap();
+        fs::create_dir_all(dir.path().join("tmp")).unwrap();
+        fs::write(dir.path().join("app/new.rb"), "").unwrap();
+        fs::write(dir.path().join("spec/new_spec.rb"), "").unwrap();
+        fs::write(dir.path().join("tmp/build.log"), "").unwrap();
+
+        assert_eq!(
+            diff_files(dir.path(), &Changes::WorkingTree).unwrap(),
+            vec![
+                PathBuf::from("app/new.rb"),
+                PathBuf::from("spec/new_spec.rb")
+            ]
+        );
+        assert_eq!(
+            diff_files(dir.path(), &Changes::Staged).unwrap(),
+            vec![] as Vec<PathBuf>
+        );
+    }
+
+    #[test]
+    fn diff_files_before_the_first_commit_lists_every_file() {
+        let dir = repo(&["app/user.rb", "spec/user_spec.rb"]);
+        git(dir.path(), &["add", "app"]);
+
+        assert_eq!(
+            diff_files(dir.path(), &Changes::WorkingTree).unwrap(),
+            vec![
+                PathBuf::from("app/user.rb"),
+                PathBuf::from("spec/user_spec.rb")
+            ]
+        );
+        assert_eq!(
+            diff_files(dir.path(), &Changes::Staged).unwrap(),
+            vec![PathBuf::from("app/user.rb")]
+        );
+    }
+
+    #[test]
+    fn diff_files_since_an_unknown_revision_fails() {
+        let dir = repo(&["app/user.rb"]);
+        git(dir.path(), &["add", "."]);
+        commit(dir.path(), "first");
+
+        assert!(diff_files(dir.path(), &Changes::Since("--output=x".to_string())).is_err());
+        assert!(!dir.path().join("x..HEAD").exists());
+    }
+}
@@ -0,0 +1,760 @@
+use alt::path::scoring::{Candidate, PathParts};
+use alt::path::utils::{cleanse_path, fnv1a, path_to_bytes, SerializedPath};
+use ignore::{DirEntry, WalkBuilder, WalkParallel, WalkState};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::fs::FileType;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod watched;
+
+// Bump whenever the format, or how the parts of paths are computed, changes
+// so that indexes written by other versions are rebuilt rather than used.
+const VERSION: u32 = 1;
+
+/// Files whose changes can change which paths a walk ignores.
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".git/info/exclude"];
+
+// A modification time this recent may still change again within the
+// granularity of the file system's timestamps, so it is not trusted.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+type Mtime = (u64, u32);
+
+/// The possible alternate paths found by walking a directory, along with
+/// their parts, kept on disk between runs so that only the directories that
+/// changed since have to be listed again.
+///
+/// A directory is listed again when its modification time changes, which
+/// happens when entries are added to, removed from or renamed within it, and
+/// the directory and all of those below it are walked again when one of its
+/// ignore files changes.
+#[derive(Debug, PartialEq)]
+pub struct Index {
+    root: PathBuf,
+    dirs: BTreeMap<PathBuf, Dir>,
+    // whether it was built or the last refresh changed it, and so it needs
+    // writing again
+    changed: bool,
+    // the number of threads to walk with, 0 meaning one per CPU
+    threads: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Dir {
+    mtime: Option<Mtime>,
+    ignore_mtimes: Option<Vec<Option<Mtime>>>,
+    // the parent part of the paths of the files, the same for all of them
+    parent: Option<String>,
+    subdirs: Vec<SerializedPath>,
+    files: Vec<File>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct File {
+    name: SerializedPath,
+    stem: Option<String>,
+    tokens: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    root: SerializedPath,
+    dirs: Vec<(SerializedPath, Dir)>,
+}
+
+/// A possible alternate path read from the index, along with its parts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedPath {
+    pub path: PathBuf,
+    pub parts: PathParts,
+}
+
+impl IndexedPath {
+    pub fn new(path: PathBuf) -> Self {
+        IndexedPath {
+            parts: PathParts::new(&path),
+            path,
+        }
+    }
+}
+
+impl Candidate for IndexedPath {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn parts(&self) -> Option<&PathParts> {
+        Some(&self.parts)
+    }
+}
+
+impl Candidate for &IndexedPath {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn parts(&self) -> Option<&PathParts> {
+        Some(&self.parts)
+    }
+}
+
+// An entry found by a walk. The modification times of a directory are taken
+// as it is found, before it is listed, so changes made while it is are found
+// next time.
+struct WalkedEntry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+    mtime: Option<Mtime>,
+    ignore_mtimes: Option<Vec<Option<Mtime>>>,
+}
+
+impl Index {
+    /// Index the files found by walking `root`, with walks built by `walk`
+    /// for the directory to walk.
+    pub fn build<F: Fn(&Path) -> WalkBuilder>(root: &Path, walk: &F) -> Self {
+        Index::build_with_threads(root, 1, walk)
+    }
+
+    /// Index the files found by walking `root` with the given number of
+    /// threads, 0 meaning one per CPU. Refreshes walk with as many.
+    pub fn build_with_threads<F: Fn(&Path) -> WalkBuilder>(
+        root: &Path,
+        threads: usize,
+        walk: &F,
+    ) -> Self {
+        let mut index = Index {
+            root: root.to_path_buf(),
+            dirs: BTreeMap::new(),
+            changed: false,
+            threads,
+        };
+        index.walk_subtree(root, walk);
+        index
+    }
+
+    /// Bring the index up to date with the files under its root, listing
+    /// only the directories that changed since it was built or refreshed.
+    /// Directories that are no longer found below the root are dropped.
+    pub fn refresh<F: Fn(&Path) -> WalkBuilder>(&mut self, walk: &F) {
+        let mut old_dirs = mem::take(&mut self.dirs);
+        self.changed = false;
+        let root = self.root.clone();
+        self.refresh_dir(&root, &mut old_dirs, walk);
+    }
+
+    fn refresh_dir<F: Fn(&Path) -> WalkBuilder>(
+        &mut self,
+        dir_path: &Path,
+        old_dirs: &mut BTreeMap<PathBuf, Dir>,
+        walk: &F,
+    ) {
+        let old_dir = match old_dirs.remove(dir_path) {
+            Some(old_dir) => old_dir,
+            None => return self.walk_subtree(dir_path, walk),
+        };
+        let metadata = match fs::metadata(dir_path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                self.changed = true;
+                return;
+            }
+        };
+        let ignore_mtimes = self.ignore_mtimes(dir_path);
+        if old_dir.ignore_mtimes.is_none() || old_dir.ignore_mtimes != ignore_mtimes {
+            return self.walk_subtree(dir_path, walk);
+        }
+
+        let mtime = trusted_mtime(&metadata);
+        let dir = if old_dir.mtime.is_some() && old_dir.mtime == mtime {
+            old_dir
+        } else {
+            self.changed = true;
+            self.list_dir(dir_path, mtime, ignore_mtimes, walk)
+        };
+        let subdirs: Vec<PathBuf> = dir
+            .subdirs
+            .iter()
+            .map(|name| dir_path.join(name.to_path_buf()))
+            .collect();
+        self.dirs.insert(dir_path.to_path_buf(), dir);
+        for subdir in subdirs {
+            self.refresh_dir(&subdir, old_dirs, walk);
+        }
+    }
+
+    // List the entries of a single directory, without descending into those
+    // that are directories themselves.
+    fn list_dir<F: Fn(&Path) -> WalkBuilder>(
+        &self,
+        dir_path: &Path,
+        mtime: Option<Mtime>,
+        ignore_mtimes: Option<Vec<Option<Mtime>>>,
+        walk: &F,
+    ) -> Dir {
+        let mut dir = Dir {
+            mtime,
+            ignore_mtimes,
+            parent: None,
+            subdirs: vec![],
+            files: vec![],
+        };
+        let mut walker = walk(dir_path);
+        walker.max_depth(Some(1));
+        self.skip_symlink_loops(&mut walker, dir_path);
+        for entry in walker.build() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            if entry.depth() == 0 {
+                continue;
+            }
+            match entry.file_type() {
+                Some(file_type) if file_type.is_dir() => dir.subdirs.push(name_of(entry.path())),
+                Some(file_type) if file_type.is_file() => add_file(&mut dir, entry.path()),
+                _ => {}
+            }
+        }
+        sort_dir(&mut dir);
+        dir
+    }
+
+    // Walk a directory and every directory below it, replacing what was
+    // indexed for them.
+    fn walk_subtree<F: Fn(&Path) -> WalkBuilder>(&mut self, dir_path: &Path, walk: &F) {
+        self.changed = true;
+        let mut walker = walk(dir_path);
+        self.skip_symlink_loops(&mut walker, dir_path);
+        let entries = match self.threads {
+            1 => walker
+                .build()
+                .filter_map(|entry| self.walked_entry(entry.ok()?))
+                .collect(),
+            threads => self.walk_parallel(walker.threads(threads).build_parallel()),
+        };
+
+        let mut dirs: BTreeMap<PathBuf, Dir> = BTreeMap::new();
+        for entry in entries {
+            if entry.file_type.is_dir() {
+                dirs.insert(
+                    entry.path.clone(),
+                    Dir {
+                        mtime: entry.mtime,
+                        ignore_mtimes: entry.ignore_mtimes,
+                        parent: None,
+                        subdirs: vec![],
+                        files: vec![],
+                    },
+                );
+            }
+            if entry.depth == 0 {
+                continue;
+            }
+            let parent = match entry.path.parent().and_then(|parent| dirs.get_mut(parent)) {
+                Some(parent) => parent,
+                None => continue,
+            };
+            if entry.file_type.is_dir() {
+                parent.subdirs.push(name_of(&entry.path));
+            } else if entry.file_type.is_file() {
+                add_file(parent, &entry.path);
+            }
+        }
+        for (path, mut dir) in dirs {
+            sort_dir(&mut dir);
+            self.dirs.insert(path, dir);
+        }
+    }
+
+    // Walk on many threads, sorting what they found by path so that each
+    // directory comes before the entries in it, as it does in a sequential
+    // walk.
+    fn walk_parallel(&self, walker: WalkParallel) -> Vec<WalkedEntry> {
+        let entries: Mutex<Vec<WalkedEntry>> = Mutex::new(vec![]);
+        walker.run(|| {
+            Box::new(|entry| {
+                if let Some(entry) = entry.ok().and_then(|entry| self.walked_entry(entry)) {
+                    entries.lock().unwrap().push(entry);
+                }
+                WalkState::Continue
+            })
+        });
+        let mut entries = entries.into_inner().unwrap();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    fn walked_entry(&self, entry: DirEntry) -> Option<WalkedEntry> {
+        let file_type = entry.file_type()?;
+        let (mtime, ignore_mtimes) = match file_type.is_dir() {
+            true => (
+                fs::metadata(entry.path())
+                    .ok()
+                    .and_then(|metadata| trusted_mtime(&metadata)),
+                self.ignore_mtimes(entry.path()),
+            ),
+            false => (None, None),
+        };
+        Some(WalkedEntry {
+            depth: entry.depth(),
+            path: entry.into_path(),
+            file_type,
+            mtime,
+            ignore_mtimes,
+        })
+    }
+
+    // Following a symlink to a directory the walk is already in would walk
+    // it again. A walk of the whole index finds these loops itself, but one
+    // of a directory below the root does not know the directories above it.
+    fn skip_symlink_loops(&self, walker: &mut WalkBuilder, dir_path: &Path) {
+        let (root, dir) = match (fs::canonicalize(&self.root), fs::canonicalize(dir_path)) {
+            (Ok(root), Ok(dir)) => (root, dir),
+            _ => return,
+        };
+        walker.filter_entry(move |entry| {
+            if !entry.path_is_symlink() || !entry.file_type().is_some_and(|t| t.is_dir()) {
+                return true;
+            }
+            match fs::canonicalize(entry.path()) {
+                Ok(target) => !(target.starts_with(&root) && dir.starts_with(&target)),
+                Err(_) => true,
+            }
+        });
+    }
+
+    // The modification times of the ignore files in the directory, along
+    // with those of the directories above it for the root, or `None` when
+    // one of them is too recent to be trusted.
+    fn ignore_mtimes(&self, dir_path: &Path) -> Option<Vec<Option<Mtime>>> {
+        let dirs: Vec<PathBuf> = if dir_path == self.root {
+            match fs::canonicalize(dir_path) {
+                Ok(dir) => dir.ancestors().map(Path::to_path_buf).collect(),
+                Err(_) => vec![dir_path.to_path_buf()],
+            }
+        } else {
+            vec![dir_path.to_path_buf()]
+        };
+
+        let mut mtimes = vec![];
+        for dir in dirs {
+            for ignore_file in IGNORE_FILES {
+                match fs::metadata(dir.join(ignore_file)) {
+                    Ok(metadata) => mtimes.push(Some(trusted_mtime(&metadata)?)),
+                    Err(_) => mtimes.push(None),
+                }
+            }
+        }
+        Some(mtimes)
+    }
+
+    /// The indexed paths, in the order a walk sorted by file name finds
+    /// them.
+    pub fn indexed_paths(&self) -> Vec<IndexedPath> {
+        let mut indexed_paths = vec![];
+        self.push_indexed_paths(&self.root, &mut indexed_paths);
+        indexed_paths
+    }
+
+    fn push_indexed_paths(&self, dir_path: &Path, indexed_paths: &mut Vec<IndexedPath>) {
+        let dir = match self.dirs.get(dir_path) {
+            Some(dir) => dir,
+            None => return,
+        };
+        let mut files = dir.files.iter().peekable();
+        let mut subdirs = dir.subdirs.iter().peekable();
+        loop {
+            let next_is_file = match (files.peek(), subdirs.peek()) {
+                (Some(file), Some(subdir)) => file.name.to_path_buf() < subdir.to_path_buf(),
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            if next_is_file {
+                let file = files.next().unwrap();
+                indexed_paths.push(IndexedPath {
+                    path: dir_path.join(file.name.to_path_buf()),
+                    parts: PathParts {
+                        stem: file.stem.clone(),
+                        parent: dir.parent.clone(),
+                        tokens: file.tokens.clone(),
+                    },
+                });
+            } else {
+                let subdir = subdirs.next().unwrap();
+                self.push_indexed_paths(&dir_path.join(subdir.to_path_buf()), indexed_paths);
+            }
+        }
+    }
+
+    /// Read an index written by `write`.
+    pub fn read(index_path: &Path) -> io::Result<Self> {
+        let contents = fs::read(index_path)?;
+        let index_file: IndexFile = serde_json::from_slice(&contents)?;
+        if index_file.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("index version {} is not {}", index_file.version, VERSION),
+            ));
+        }
+        Ok(Index {
+            root: index_file.root.to_path_buf(),
+            dirs: index_file
+                .dirs
+                .into_iter()
+                .map(|(path, dir)| (path.to_path_buf(), dir))
+                .collect(),
+            changed: false,
+            threads: 1,
+        })
+    }
+
+    /// Write the index, replacing any previously written to the same path
+    /// all at once so that a concurrent run never reads half of it.
+    pub fn write(&self, index_path: &Path) -> io::Result<()> {
+        let index_file = IndexFile {
+            version: VERSION,
+            root: SerializedPath::new(&self.root),
+            dirs: self
+                .dirs
+                .iter()
+                .map(|(path, dir)| (SerializedPath::new(path), dir.clone()))
+                .collect(),
+        };
+        if let Some(dir) = index_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = index_path.with_extension(format!("{}.tmp", process::id()));
+        fs::write(&temp_path, serde_json::to_vec(&index_file)?)?;
+        fs::rename(&temp_path, index_path).inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        })
+    }
+}
+
+/// Load the index of `root` from `index_path` and refresh it, or build it
+/// when there is none, writing it back for the next run. Either walks with
+/// the given number of threads, 0 meaning one per CPU. The index is only a
+/// cache, so failing to read or write it falls back to walking.
+pub fn load_index<F: Fn(&Path) -> WalkBuilder>(
+    index_path: &Path,
+    root: &Path,
+    threads: usize,
+    walk: &F,
+) -> Index {
+    let index = match Index::read(index_path) {
+        Ok(mut index) if index.root == root => {
+            index.threads = threads;
+            index.refresh(walk);
+            index
+        }
+        _ => Index::build_with_threads(root, threads, walk),
+    };
+    if index.changed {
+        let _ = index.write(index_path);
+    }
+    index
+}
+
+/// Where to keep the index of the current directory: within the `.git`
+/// directory of the repository it is in, or in the user's cache directory
+/// otherwise. Each directory alt is run in, and whether hidden entries are
+/// included, gets an index of its own.
+pub fn index_path(cwd: &Path, include_hidden: bool) -> Option<PathBuf> {
+    let key = |dir: &Path| {
+        format!(
+            "{:016x}.json",
+            fnv1a(&[&path_to_bytes(dir), &[include_hidden as u8]])
+        )
+    };
+
+    for dir in cwd.ancestors() {
+        let git_dir = dir.join(".git");
+        if git_dir.is_dir() {
+            let relative = cwd.strip_prefix(dir).unwrap_or(cwd);
+            return Some(git_dir.join("alt").join("index").join(key(relative)));
+        }
+    }
+
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("alt").join("index").join(key(cwd)))
+}
+
+fn name_of(path: &Path) -> SerializedPath {
+    SerializedPath::new(path.file_name().map(Path::new).unwrap_or(path))
+}
+
+fn add_file(dir: &mut Dir, path: &Path) {
+    let parts = PathParts::new(&cleanse_path(path));
+    dir.parent = parts.parent;
+    dir.files.push(File {
+        name: name_of(path),
+        stem: parts.stem,
+        tokens: parts.tokens,
+    });
+}
+
+// Sort the entries of a directory the way a walk sorted by file name does.
+fn sort_dir(dir: &mut Dir) {
+    dir.subdirs.sort_by_cached_key(SerializedPath::to_path_buf);
+    dir.files.sort_by_cached_key(|file| file.name.to_path_buf());
+}
+
+fn trusted_mtime(metadata: &fs::Metadata) -> Option<Mtime> {
+    let modified = metadata.modified().ok()?;
+    if SystemTime::now().duration_since(modified).ok()? < RACY_WINDOW {
+        return None;
+    }
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{index_path, load_index, Index};
+    use alt::path::scoring::PathParts;
+    use ignore::WalkBuilder;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn walk(root: &Path) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(root);
+        builder.follow_links(true).hidden(true);
+        builder
+    }
+
+    fn sorted_walk(root: &Path) -> Vec<PathBuf> {
+        walk(root)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .map(|entry| entry.into_path())
+            .collect()
+    }
+
+    fn indexed_paths(index: Index) -> Vec<PathBuf> {
+        index
+            .indexed_paths()
+            .into_iter()
+            .map(|indexed_path| indexed_path.path)
+            .collect()
+    }
+
+    fn create_files(root: &Path, paths: &[&str]) {
+        for path in paths {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+    }
+
+    fn long_ago(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000_000 + secs)
+    }
+
+    fn set_mtime(path: &Path, mtime: SystemTime) {
+        fs::File::open(path).unwrap().set_modified(mtime).unwrap();
+    }
+
+    // Make every entry look like it was last changed long enough ago for its
+    // modification time to be trusted.
+    fn age(root: &Path, mtime: SystemTime) {
+        for entry in WalkBuilder::new(root).hidden(false).build() {
+            set_mtime(entry.unwrap().path(), mtime);
+        }
+    }
+
+    #[test]
+    fn index_has_the_paths_of_a_sorted_walk_along_with_their_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(
+            dir.path(),
+            &[
+                "b.rs",
+                "a/z_test.rs",
+                "a/b/c.rs",
+                "a.rs",
+                "aa/x.rs",
+                ".hidden/h.rs",
+            ],
+        );
+
+        let indexed = Index::build(dir.path(), &walk).indexed_paths();
+
+        let paths: Vec<PathBuf> = indexed.iter().map(|i| i.path.clone()).collect();
+        assert_eq!(paths, sorted_walk(dir.path()));
+        for indexed_path in indexed {
+            assert_eq!(indexed_path.parts, PathParts::new(&indexed_path.path));
+        }
+    }
+
+    #[test]
+    fn index_is_the_same_after_being_written_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["src/lib.rs", "tests/lib_test.rs"]);
+        age(dir.path(), long_ago(0));
+        let index = Index::build(dir.path(), &walk);
+        let index_path = dir.path().join("cache/index.json");
+
+        index.write(&index_path).unwrap();
+        let read = Index::read(&index_path).unwrap();
+
+        assert_eq!(read.dirs, index.dirs);
+        assert_eq!(read.root, index.root);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn index_keeps_names_that_are_not_utf8_when_written_and_read() {
+        use alt::path::utils::path_from_bytes;
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = path_from_bytes(b"caf\xe9.rb".to_vec());
+        fs::write(dir.path().join(&name), "").unwrap();
+        let index_path = dir.path().join("index.json");
+
+        Index::build(dir.path(), &walk).write(&index_path).unwrap();
+
+        assert_eq!(
+            indexed_paths(Index::read(&index_path).unwrap()),
+            vec![dir.path().join(&name)]
+        );
+    }
+
+    #[test]
+    fn index_built_in_parallel_is_the_same_as_one_built_sequentially() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(
+            dir.path(),
+            &["b.rs", "a/z_test.rs", "a/b/c.rs", "a-b/d.rs", "aa/x.rs"],
+        );
+        age(dir.path(), long_ago(0));
+
+        let sequential = Index::build(dir.path(), &walk);
+        let parallel = Index::build_with_threads(dir.path(), 4, &walk);
+
+        assert_eq!(parallel.dirs, sequential.dirs);
+    }
+
+    #[test]
+    fn load_index_builds_and_refreshes_with_the_given_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["src/a/one.rs", "src/b/two.rs"]);
+        let index_path = dir.path().join("cache/index.json");
+
+        let built = load_index(&index_path, &dir.path().join("src"), 4, &walk);
+        assert_eq!(built.threads, 4);
+
+        create_files(dir.path(), &["src/c/d/three.rs"]);
+        let refreshed = load_index(&index_path, &dir.path().join("src"), 2, &walk);
+        assert_eq!(refreshed.threads, 2);
+        assert_eq!(
+            indexed_paths(refreshed),
+            sorted_walk(&dir.path().join("src"))
+        );
+    }
+
+    #[test]
+    fn refresh_finds_added_and_removed_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a/one.rs", "b/two.rs", "c/d/three.rs"]);
+        age(dir.path(), long_ago(0));
+        let mut index = Index::build(dir.path(), &walk);
+
+        create_files(dir.path(), &["a/four.rs", "c/e/f/five.rs"]);
+        fs::remove_dir_all(dir.path().join("b")).unwrap();
+        fs::remove_file(dir.path().join("c/d/three.rs")).unwrap();
+
+        index.refresh(&walk);
+        assert_eq!(indexed_paths(index), sorted_walk(dir.path()));
+    }
+
+    #[test]
+    fn refresh_only_lists_directories_whose_mtime_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a/one.rs", "b/two.rs"]);
+        age(dir.path(), long_ago(0));
+        let mut index = Index::build(dir.path(), &walk);
+
+        // b looks unchanged as its modification time is put back
+        create_files(dir.path(), &["a/three.rs", "b/four.rs"]);
+        set_mtime(&dir.path().join("b"), long_ago(0));
+
+        index.refresh(&walk);
+        assert!(index.changed);
+        assert_eq!(
+            indexed_paths(index),
+            vec![
+                dir.path().join("a/one.rs"),
+                dir.path().join("a/three.rs"),
+                dir.path().join("b/two.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn refresh_leaves_the_index_unchanged_when_nothing_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a/one.rs", "b/two.rs"]);
+        age(dir.path(), long_ago(0));
+        let mut index = Index::build(dir.path(), &walk);
+        let dirs = index.dirs.clone();
+
+        index.refresh(&walk);
+
+        assert!(!index.changed);
+        assert_eq!(index.dirs, dirs);
+    }
+
+    #[test]
+    fn refresh_walks_a_directory_again_when_its_ignore_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a/one.rs", "a/b/two.rs", "a/b/c/three.rs"]);
+        fs::write(dir.path().join("a/.ignore"), "three.rs\n").unwrap();
+        age(dir.path(), long_ago(0));
+        let mut index = Index::build(dir.path(), &walk);
+
+        // only the ignore file changes, none of the directories do
+        fs::write(dir.path().join("a/.ignore"), "two.rs\n").unwrap();
+        set_mtime(&dir.path().join("a/.ignore"), long_ago(1));
+
+        index.refresh(&walk);
+        assert_eq!(
+            indexed_paths(index),
+            vec![
+                dir.path().join("a/b/c/three.rs"),
+                dir.path().join("a/one.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn index_path_is_within_the_git_directory_of_the_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("app/models");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+
+        let index_path = index_path(&nested, false).unwrap();
+
+        assert!(index_path.starts_with(dir.path().join(".git/alt/index")));
+        assert_ne!(index_path, super::index_path(&nested, true).unwrap());
+        assert_ne!(index_path, super::index_path(dir.path(), false).unwrap());
+    }
+}
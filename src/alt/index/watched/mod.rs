@@ -0,0 +1,170 @@
+use alt::index::{load_index, Index, IndexedPath, IGNORE_FILES};
+use ignore::WalkBuilder;
+use notify;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// An index kept in memory by a long running process, refreshed only when
+/// the file system reports that paths below its root were created, removed
+/// or renamed, or that an ignore file changed.
+///
+/// When the file system can not be watched, e.g. because the limit on the
+/// number of watches was reached, it is refreshed every time its paths are
+/// asked for instead, which still only lists the directories that changed.
+pub struct WatchedIndex {
+    index: Index,
+    indexed_paths: Vec<IndexedPath>,
+    changed: Arc<AtomicBool>,
+    watcher: Option<RecommendedWatcher>,
+    walk: Box<dyn Fn(&Path) -> WalkBuilder + Send>,
+}
+
+impl WatchedIndex {
+    /// Watch `root` and load its index, from `index_path` when given, with
+    /// walks built by `walk` for the directory to walk, on the given number of
+    /// threads, 0 meaning one per CPU.
+    pub fn new<F>(root: &Path, index_path: Option<&Path>, threads: usize, walk: F) -> Self
+    where
+        F: Fn(&Path) -> WalkBuilder + Send + 'static,
+    {
+        let changed = Arc::new(AtomicBool::new(false));
+        // watching starts before the index is loaded so no change is missed
+        let watcher = watch(root, changed.clone()).ok();
+        let index = match index_path {
+            Some(index_path) => load_index(index_path, root, threads, &walk),
+            None => Index::build_with_threads(root, threads, &walk),
+        };
+        WatchedIndex {
+            indexed_paths: index.indexed_paths(),
+            index,
+            changed,
+            watcher,
+            walk: Box::new(walk),
+        }
+    }
+
+    /// The indexed paths, refreshed first if anything changed since they
+    /// were last asked for.
+    pub fn indexed_paths(&mut self) -> &[IndexedPath] {
+        if self.watcher.is_none() || self.changed.swap(false, Ordering::SeqCst) {
+            self.index.refresh(&self.walk);
+            if self.index.changed {
+                self.indexed_paths = self.index.indexed_paths();
+            }
+        }
+        &self.indexed_paths
+    }
+}
+
+fn watch(root: &Path, changed: Arc<AtomicBool>) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        // errors may mean events were missed
+        if event.map_or(true, |event| changes_paths(&event)) {
+            changed.store(true, Ordering::SeqCst);
+        }
+    })?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+// Whether the event may change the paths a walk finds. Files being read or
+// written to do not, unless they are ignore files.
+fn changes_paths(event: &Event) -> bool {
+    let is_ignore_file = |path: &PathBuf| {
+        IGNORE_FILES
+            .iter()
+            .any(|ignore_file| path.ends_with(ignore_file))
+    };
+
+    event.need_rescan()
+        || event.paths.iter().any(is_ignore_file)
+        || !matches!(
+            event.kind,
+            EventKind::Access(_)
+                | EventKind::Modify(ModifyKind::Data(_))
+                | EventKind::Modify(ModifyKind::Metadata(_))
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{changes_paths, WatchedIndex};
+    use ignore::WalkBuilder;
+    use notify::event::{AccessKind, CreateKind, DataChange, ModifyKind, RenameMode};
+    use notify::{Event, EventKind};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(PathBuf::from(path))
+    }
+
+    #[test]
+    fn changes_paths_when_paths_are_created_removed_or_renamed() {
+        assert!(changes_paths(&event(
+            EventKind::Create(CreateKind::File),
+            "src/lib.rs"
+        )));
+        assert!(changes_paths(&event(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            "src/lib.rs"
+        )));
+        assert!(!changes_paths(&event(
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            "src/lib.rs"
+        )));
+        assert!(!changes_paths(&event(
+            EventKind::Access(AccessKind::Any),
+            "src/lib.rs"
+        )));
+    }
+
+    #[test]
+    fn changes_paths_when_an_ignore_file_is_written_to() {
+        assert!(changes_paths(&event(
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            "src/.gitignore"
+        )));
+        assert!(changes_paths(&event(
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            "repo/.git/info/exclude"
+        )));
+    }
+
+    #[test]
+    fn watched_index_finds_files_created_after_it_was_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let mut watched =
+            WatchedIndex::new(dir.path(), None, 2, |root: &Path| WalkBuilder::new(root));
+        assert_eq!(watched.index.threads, 2);
+
+        fs::write(dir.path().join("src/lib_test.rs"), "").unwrap();
+
+        // the file system reports the change in the background
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while watched.indexed_paths().len() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let paths: Vec<PathBuf> = watched
+            .indexed_paths()
+            .iter()
+            .map(|indexed_path| indexed_path.path.clone())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                dir.path().join("src/lib.rs"),
+                dir.path().join("src/lib_test.rs")
+            ]
+        );
+    }
+}
@@ -0,0 +1,418 @@
+use alt::path::scoring::ScoredPath;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, event, execute, queue, terminal};
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// How much of the selected file is read to preview it.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+const TAB: &str = "    ";
+const HELP: &str = "enter: print  ctrl-e: open in $EDITOR  esc: cancel";
+
+/// What was chosen to be done with the chosen alternate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    Print(PathBuf),
+    Edit(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Continue,
+    Choose(Choice),
+    Cancel,
+}
+
+/// The state of the picker: the ranked alternates, the ones matching the
+/// query typed so far and which of those is selected.
+pub struct Picker {
+    alternates: Vec<ScoredPath>,
+    query: String,
+    // indexes of the matching alternates, in rank order
+    matches: Vec<usize>,
+    selected: usize,
+    offset: usize,
+}
+
+impl Picker {
+    pub fn new(alternates: Vec<ScoredPath>) -> Self {
+        let matches = (0..alternates.len()).collect();
+        Picker {
+            alternates,
+            query: String::new(),
+            matches,
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The alternates matching the query, best ranked first.
+    pub fn matches(&self) -> impl Iterator<Item = &ScoredPath> {
+        self.matches
+            .iter()
+            .map(move |index| &self.alternates[*index])
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.matches
+            .get(self.selected)
+            .map(|index| self.alternates[*index].1.as_path())
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => self.choose(Choice::Print),
+            KeyCode::Char('e') if ctrl => self.choose(Choice::Edit),
+            KeyCode::Esc => Action::Cancel,
+            KeyCode::Char('c') if ctrl => Action::Cancel,
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::Char('u') if ctrl => self.set_query(String::new()),
+            KeyCode::Backspace => {
+                let mut query = self.query.clone();
+                query.pop();
+                self.set_query(query)
+            }
+            KeyCode::Char(c) if !ctrl => {
+                let query = format!("{}{}", self.query, c);
+                self.set_query(query)
+            }
+            _ => Action::Continue,
+        }
+    }
+
+    fn choose(&self, choice: fn(PathBuf) -> Choice) -> Action {
+        match self.selected() {
+            Some(path) => Action::Choose(choice(path.to_path_buf())),
+            None => Action::Continue,
+        }
+    }
+
+    fn move_selection(&mut self, by: isize) -> Action {
+        let last = self.matches.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + by).clamp(0, last) as usize;
+        Action::Continue
+    }
+
+    // Narrow the alternates down to those matching the query, keeping their
+    // ranked order and selecting the best of them.
+    fn set_query(&mut self, query: String) -> Action {
+        self.matches = (0..self.alternates.len())
+            .filter(|index| {
+                fuzzy_match(&query, &self.alternates[*index].1.to_string_lossy()).is_some()
+            })
+            .collect();
+        self.query = query;
+        self.selected = 0;
+        self.offset = 0;
+        Action::Continue
+    }
+
+    // Scroll the list so that the selected alternate is shown.
+    fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if height > 0 && self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+    }
+}
+
+/// The positions of the characters of `text` matching the query, in order,
+/// when each of its space separated terms matches. A term matches when its
+/// characters appear in the text in the same order, ignoring case unless
+/// the term has upper case characters.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let text: Vec<char> = text.chars().collect();
+    let mut positions: Vec<usize> = vec![];
+    for term in query.split_whitespace() {
+        let ignore_case = !term.chars().any(char::is_uppercase);
+        let eq = |a: char, b: char| match ignore_case {
+            true => a.to_lowercase().eq(b.to_lowercase()),
+            false => a == b,
+        };
+        let mut start = 0;
+        for c in term.chars() {
+            let found = start + text[start..].iter().position(|t| eq(*t, c))?;
+            positions.push(found);
+            start = found + 1;
+        }
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    Some(positions)
+}
+
+/// The first lines of the file, as they can be shown in a terminal.
+pub fn preview(path: &Path, max_lines: usize) -> Vec<String> {
+    let mut contents: Vec<u8> = vec![];
+    let read =
+        File::open(path).and_then(|file| file.take(PREVIEW_BYTES).read_to_end(&mut contents));
+    if let Err(e) = read {
+        return vec![format!("(can not be previewed, {})", e)];
+    }
+    if contents.contains(&0) {
+        return vec!["(binary file)".to_string()];
+    }
+    String::from_utf8_lossy(&contents)
+        .lines()
+        .take(max_lines)
+        .map(|line| {
+            line.replace('\t', TAB)
+                .chars()
+                .map(|c| if c.is_control() { '?' } else { c })
+                .collect()
+        })
+        .collect()
+}
+
+/// Let the user narrow down and choose among the ranked alternates on the
+/// terminal, drawn on stderr so stdout can be captured. None when the user
+/// cancelled.
+pub fn pick(alternates: Vec<ScoredPath>) -> io::Result<Option<Choice>> {
+    let mut picker = Picker::new(alternates);
+    let mut out = io::stderr();
+
+    let _terminal = TerminalGuard::enter(&mut out)?;
+    loop {
+        draw(&mut out, &mut picker, terminal::size()?)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match picker.handle_key(key) {
+                Action::Continue => {}
+                Action::Choose(choice) => return Ok(Some(choice)),
+                Action::Cancel => return Ok(None),
+            }
+        }
+    }
+}
+
+// Puts the terminal in raw mode on the alternate screen, restoring it when
+// dropped, including when picking fails.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter<W: Write>(out: &mut W) -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(out, EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Draw the query, the matching alternates with their scores, a preview of
+// the selected one and the keys that can be used, top to bottom.
+fn draw<W: Write>(out: &mut W, picker: &mut Picker, (width, height): (u16, u16)) -> io::Result<()> {
+    let (width, height) = (width as usize, height as usize);
+    // the query, separator and help take a row each
+    let list_height = height.saturating_sub(3) / 2;
+    picker.scroll(list_height);
+
+    queue!(out, Clear(ClearType::All))?;
+    // the number of matches is aligned right, after the query
+    let prompt = format!("> {} ", picker.query());
+    let count = format!("{}/{}", picker.matches.len(), picker.alternates.len());
+    let padding = width.saturating_sub(prompt.chars().count() + count.len());
+    draw_row(
+        out,
+        0,
+        &format!("{}{}{}", prompt, " ".repeat(padding), count),
+        width,
+    )?;
+
+    let rows: Vec<(bool, String, String)> = picker
+        .matches()
+        .enumerate()
+        .skip(picker.offset)
+        .take(list_height)
+        .map(|(index, (score, path))| {
+            (
+                index == picker.selected,
+                format!("{:>10.2}  ", score),
+                path.to_string_lossy().into_owned(),
+            )
+        })
+        .collect();
+    for (row, (selected, score, path)) in rows.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, row as u16 + 1))?;
+        if *selected {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        let score: String = score.chars().take(width).collect();
+        queue!(out, Print(&score))?;
+        // the characters of the path matching the query are in bold
+        let highlighted = fuzzy_match(picker.query(), path).unwrap_or_default();
+        for (i, c) in path.chars().take(width - score.chars().count()).enumerate() {
+            if highlighted.binary_search(&i).is_ok() {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Bold),
+                    Print(c),
+                    SetAttribute(Attribute::NormalIntensity)
+                )?;
+            } else {
+                queue!(out, Print(c))?;
+            }
+        }
+        queue!(out, SetAttribute(Attribute::Reset))?;
+    }
+
+    let separator_row = list_height + 1;
+    let selected = picker.selected().map(Path::to_path_buf);
+    let title = selected.as_ref().map_or(String::new(), |path| {
+        format!(" {} ", path.to_string_lossy())
+    });
+    draw_row(
+        out,
+        separator_row,
+        &format!("──{}{}", title, "─".repeat(width)),
+        width,
+    )?;
+    if let Some(path) = selected {
+        let preview_height = height.saturating_sub(separator_row + 2);
+        for (row, line) in preview(&path, preview_height).iter().enumerate() {
+            draw_row(out, separator_row + 1 + row, line, width)?;
+        }
+    }
+
+    queue!(out, SetAttribute(Attribute::Dim))?;
+    draw_row(out, height.saturating_sub(1), HELP, width)?;
+    queue!(out, SetAttribute(Attribute::Reset))?;
+    out.flush()
+}
+
+fn draw_row<W: Write>(out: &mut W, row: usize, text: &str, width: usize) -> io::Result<()> {
+    let text: String = text.chars().take(width).collect();
+    queue!(out, cursor::MoveTo(0, row as u16), Print(text))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{fuzzy_match, preview, Action, Choice, Picker};
+    use alt::path::scoring::ScoredPath;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn picker() -> Picker {
+        let alternates: Vec<ScoredPath> = vec![
+            (3.0, PathBuf::from("app/models/user.rb")),
+            (2.0, PathBuf::from("spec/models/user_spec.rb")),
+            (1.0, PathBuf::from("spec/factories/users.rb")),
+        ];
+        Picker::new(alternates)
+    }
+
+    fn matches(picker: &Picker) -> Vec<&Path> {
+        picker.matches().map(|(_, path)| path.as_path()).collect()
+    }
+
+    #[test]
+    fn fuzzy_match_matches_characters_in_order() {
+        assert_eq!(fuzzy_match("spc", "spec/user.rb"), Some(vec![0, 1, 3]));
+        assert_eq!(fuzzy_match("cps", "spec/user.rb"), None);
+        assert_eq!(fuzzy_match("", "spec/user.rb"), Some(vec![]));
+    }
+
+    #[test]
+    fn fuzzy_match_ignores_case_unless_the_term_has_upper_case() {
+        assert!(fuzzy_match("readme", "README.md").is_some());
+        assert!(fuzzy_match("ReadMe", "README.md").is_none());
+    }
+
+    #[test]
+    fn fuzzy_match_requires_every_term_to_match() {
+        assert_eq!(fuzzy_match("rb us", "user.rb"), Some(vec![0, 1, 3, 6]));
+        assert_eq!(fuzzy_match("rb js", "user.rb"), None);
+    }
+
+    #[test]
+    fn typing_narrows_the_alternates_keeping_their_rank() {
+        let mut picker = picker();
+
+        for c in "spec".chars() {
+            picker.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(
+            matches(&picker),
+            vec![
+                Path::new("spec/models/user_spec.rb"),
+                Path::new("spec/factories/users.rb")
+            ]
+        );
+
+        picker.handle_key(ctrl('u'));
+        assert_eq!(matches(&picker).len(), 3);
+    }
+
+    #[test]
+    fn arrow_keys_select_within_the_matches() {
+        let mut picker = picker();
+
+        picker.handle_key(key(KeyCode::Up));
+        assert_eq!(picker.selected(), Some(Path::new("app/models/user.rb")));
+        for _ in 0..5 {
+            picker.handle_key(key(KeyCode::Down));
+        }
+        assert_eq!(
+            picker.selected(),
+            Some(Path::new("spec/factories/users.rb"))
+        );
+
+        assert_eq!(
+            picker.handle_key(ctrl('e')),
+            Action::Choose(Choice::Edit(PathBuf::from("spec/factories/users.rb")))
+        );
+    }
+
+    #[test]
+    fn enter_with_no_matches_chooses_nothing() {
+        let mut picker = picker();
+        picker.handle_key(key(KeyCode::Char('z')));
+
+        assert_eq!(picker.handle_key(key(KeyCode::Enter)), Action::Continue);
+        assert_eq!(picker.handle_key(key(KeyCode::Esc)), Action::Cancel);
+    }
+
+    #[test]
+    fn preview_shows_the_first_lines_of_text_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("user.rb");
+        fs::write(&text, "class User\n\tdef name\x1b\nend\n").unwrap();
+        let binary = dir.path().join("user.png");
+        fs::write(&binary, b"\x89PNG\x00").unwrap();
+
+        assert_eq!(preview(&text, 2), vec!["class User", "    def name?"]);
+        assert_eq!(preview(&binary, 2), vec!["(binary file)"]);
+    }
+}
@@ -0,0 +1,556 @@
+use alt::config::PartialConfig;
+use alt::path::kind::{classify, FileKind};
+use alt::path::scoring::ScoredPath;
+use alt::path::utils::{path_from_bytes, path_to_bytes};
+use alt::rpc::{
+    parse_params, read_request, write_message, Response, ResponseError, INVALID_PARAMS,
+    INVALID_REQUEST, METHOD_NOT_FOUND, REQUEST_FAILED,
+};
+use alt::session::Session;
+use serde_json;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// The error the Language Server Protocol defines for requests sent before
+/// the server was initialized.
+pub const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// Answer with the ranked alternates of the file given by its URI, along
+/// with optional options overriding the configuration.
+pub const ALTERNATES_COMMAND: &str = "alt.alternates";
+/// Ask the client to show the file given by its URI, the command of the
+/// code lenses.
+pub const OPEN_COMMAND: &str = "alt.open";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    #[serde(default)]
+    root_uri: Option<String>,
+    #[serde(default)]
+    root_path: Option<String>,
+    #[serde(default)]
+    workspace_folders: Option<Vec<WorkspaceFolder>>,
+    #[serde(default)]
+    capabilities: Value,
+}
+
+#[derive(Deserialize)]
+struct WorkspaceFolder {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentParams {
+    text_document: TextDocumentIdentifier,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+struct ExecuteCommandParams {
+    command: String,
+    #[serde(default)]
+    arguments: Vec<Value>,
+}
+
+#[derive(PartialEq)]
+enum State {
+    Uninitialized,
+    Initialized,
+    ShutDown,
+}
+
+// The server's side of the protocol, answering the requests of one client.
+struct Server<'w, W: 'w> {
+    writer: &'w mut W,
+    state: State,
+    session: Option<Session>,
+    show_document: bool,
+    next_request_id: u64,
+}
+
+/// Serve a Language Server Protocol client until it asks the server to exit
+/// or the input ends. The session is started when the client initializes
+/// the server, with the root of its workspace if it has one. Returns whether
+/// the client asked the server to shut down before exiting, as the exit
+/// code depends on it.
+pub fn serve<R, W, F>(reader: &mut R, writer: &mut W, start_session: F) -> io::Result<bool>
+where
+    R: BufRead,
+    W: Write,
+    F: FnOnce(Option<PathBuf>) -> Result<Session, String>,
+{
+    let mut start_session = Some(start_session);
+    let mut server = Server {
+        writer,
+        state: State::Uninitialized,
+        session: None,
+        show_document: false,
+        next_request_id: 0,
+    };
+
+    while let Some(request) = read_request(reader, server.writer)? {
+        if request.method == "exit" {
+            return Ok(server.state == State::ShutDown);
+        }
+        let result = match (&server.state, request.method.as_str()) {
+            (State::Uninitialized, "initialize") => {
+                let start_session = start_session.take().expect("initialized only once");
+                server.initialize(request.params, start_session)
+            }
+            (State::Uninitialized, _) => Err(ResponseError::new(
+                SERVER_NOT_INITIALIZED,
+                "The server has not been initialized".to_string(),
+            )),
+            (State::ShutDown, _) => Err(ResponseError::new(
+                INVALID_REQUEST,
+                "The server has been shut down".to_string(),
+            )),
+            (State::Initialized, "shutdown") => {
+                server.state = State::ShutDown;
+                Ok(Value::Null)
+            }
+            (State::Initialized, method) => server.handle_request(method, request.params),
+        };
+        if let Some(id) = request.id {
+            write_message(server.writer, &Response::new(id, result))?;
+        }
+    }
+    Ok(false)
+}
+
+impl<'w, W: Write> Server<'w, W> {
+    fn initialize<F>(&mut self, params: Value, start_session: F) -> Result<Value, ResponseError>
+    where
+        F: FnOnce(Option<PathBuf>) -> Result<Session, String>,
+    {
+        let params: InitializeParams = parse_params(params)?;
+        let root = params
+            .root_uri
+            .as_deref()
+            .or_else(|| {
+                params
+                    .workspace_folders
+                    .as_ref()
+                    .and_then(|folders| folders.first())
+                    .map(|folder| folder.uri.as_str())
+            })
+            .and_then(uri_to_path)
+            .or_else(|| params.root_path.as_deref().map(PathBuf::from));
+        self.session =
+            Some(start_session(root).map_err(|e| ResponseError::new(REQUEST_FAILED, e))?);
+        self.show_document = params.capabilities["window"]["showDocument"]["support"]
+            .as_bool()
+            .unwrap_or(false);
+        self.state = State::Initialized;
+
+        Ok(serde_json::json!({
+            "capabilities": {
+                "codeLensProvider": {"resolveProvider": false},
+                "documentLinkProvider": {"resolveProvider": false},
+                "executeCommandProvider": {"commands": [ALTERNATES_COMMAND, OPEN_COMMAND]},
+            },
+            "serverInfo": {"name": "alt", "version": env!("CARGO_PKG_VERSION")},
+        }))
+    }
+
+    fn handle_request(&mut self, method: &str, params: Value) -> Result<Value, ResponseError> {
+        match method {
+            // the client letting the server know it got the response to
+            // initialize, nothing to do
+            "initialized" => Ok(Value::Null),
+            "textDocument/codeLens" => {
+                let params: TextDocumentParams = parse_params(params)?;
+                let lenses: Vec<Value> = self
+                    .goto_targets(&params.text_document.uri)?
+                    .into_iter()
+                    .map(|(kind, uri)| {
+                        serde_json::json!({
+                            "range": range(0, 0),
+                            "command": {
+                                "title": format!("Go to {}", kind),
+                                "command": OPEN_COMMAND,
+                                "arguments": [uri],
+                            },
+                        })
+                    })
+                    .collect();
+                Ok(Value::from(lenses))
+            }
+            "textDocument/documentLink" => {
+                let params: TextDocumentParams = parse_params(params)?;
+                let uri = params.text_document.uri;
+                // links over the same text would hide each other, so only the
+                // best alternate is linked, from the first line
+                let links: Vec<Value> = self
+                    .goto_targets(&uri)?
+                    .into_iter()
+                    .take(1)
+                    .map(|(kind, target)| {
+                        let first_line_len = uri_to_path(&uri)
+                            .and_then(|path| fs::read(path).ok())
+                            .map_or(0, |contents| first_line_len(&contents));
+                        serde_json::json!({
+                            "range": range(0, first_line_len),
+                            "target": target,
+                            "tooltip": format!("Go to {}", kind),
+                        })
+                    })
+                    .collect();
+                Ok(Value::from(links))
+            }
+            "workspace/executeCommand" => {
+                let params: ExecuteCommandParams = parse_params(params)?;
+                self.execute_command(&params.command, params.arguments)
+            }
+            _ => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}", method),
+            )),
+        }
+    }
+
+    fn execute_command(
+        &mut self,
+        command: &str,
+        arguments: Vec<Value>,
+    ) -> Result<Value, ResponseError> {
+        let mut arguments = arguments.into_iter();
+        let uri = match arguments.next() {
+            Some(Value::String(uri)) => uri,
+            _ => {
+                return Err(ResponseError::new(
+                    INVALID_PARAMS,
+                    format!("Invalid arguments, {} takes the URI of a file", command),
+                ))
+            }
+        };
+
+        match command {
+            ALTERNATES_COMMAND => {
+                let options: PartialConfig = match arguments.next() {
+                    Some(options) => parse_params(options)?,
+                    None => PartialConfig::default(),
+                };
+                let (_, scored_paths) = self.alternates(&uri, options)?;
+                let alternates: Vec<Value> = scored_paths
+                    .into_iter()
+                    .map(|(score, path)| {
+                        serde_json::json!({
+                            "uri": path_to_uri(&self.session().cwd().join(&path)),
+                            "path": path.to_string_lossy(),
+                            "score": score,
+                            "kind": classify(&path).to_string(),
+                        })
+                    })
+                    .collect();
+                Ok(Value::from(alternates))
+            }
+            OPEN_COMMAND => {
+                if !self.show_document {
+                    return Err(ResponseError::new(
+                        REQUEST_FAILED,
+                        "The client does not support showing documents".to_string(),
+                    ));
+                }
+                self.next_request_id += 1;
+                let show_document = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": format!("alt-{}", self.next_request_id),
+                    "method": "window/showDocument",
+                    "params": {"uri": uri, "takeFocus": true},
+                });
+                write_message(self.writer, &show_document)
+                    .map_err(|e| ResponseError::new(REQUEST_FAILED, e.to_string()))?;
+                Ok(Value::Null)
+            }
+            _ => Err(ResponseError::new(
+                INVALID_PARAMS,
+                format!(
+                    "Unknown command {}, expected {} or {}",
+                    command, ALTERNATES_COMMAND, OPEN_COMMAND
+                ),
+            )),
+        }
+    }
+
+    fn session(&self) -> &Session {
+        self.session
+            .as_ref()
+            .expect("initialized servers have a session")
+    }
+
+    // Rank the alternates of the file, along with its path relative to the
+    // workspace. Files outside the workspace have no alternates.
+    fn alternates(
+        &mut self,
+        uri: &str,
+        options: PartialConfig,
+    ) -> Result<(PathBuf, Vec<ScoredPath>), ResponseError> {
+        let session = self
+            .session
+            .as_mut()
+            .expect("initialized servers have a session");
+        let path = match uri_to_path(uri).and_then(|path| relative_path(session.cwd(), &path)) {
+            Some(path) => path,
+            None => return Ok((PathBuf::new(), vec![])),
+        };
+        let config = session.config(options);
+        let scored_paths = session
+            .alternates(&config, &path)
+            .map_err(|e| ResponseError::new(REQUEST_FAILED, e.to_string()))?;
+        Ok((path, scored_paths))
+    }
+
+    // The URIs of the files to go to from the file, with their kinds.
+    fn goto_targets(&mut self, uri: &str) -> Result<Vec<(FileKind, String)>, ResponseError> {
+        let (query_path, scored_paths) = self.alternates(uri, PartialConfig::default())?;
+        let cwd = self.session().cwd();
+        Ok(goto_targets(&query_path, &scored_paths)
+            .into_iter()
+            .map(|(kind, path)| (kind, path_to_uri(&cwd.join(path))))
+            .collect())
+    }
+}
+
+/// The best ranked alternate of each kind other than the kind of the query
+/// path. The one of the opposite kind, e.g. the implementation for a test,
+/// comes first and the others follow in rank order.
+pub fn goto_targets<'p>(
+    query_path: &Path,
+    scored_paths: &'p [ScoredPath],
+) -> Vec<(FileKind, &'p Path)> {
+    let mut targets: Vec<(FileKind, &Path)> = vec![];
+    let query_kind = classify(query_path);
+    for (_, path) in scored_paths {
+        let kind = classify(path);
+        if kind != query_kind && targets.iter().all(|(target_kind, _)| *target_kind != kind) {
+            targets.push((kind, path));
+        }
+    }
+    targets.sort_by_key(|(kind, _)| *kind != query_kind.opposite());
+    targets
+}
+
+// The path relative to the root of the workspace, the form possible
+// alternates are ranked in.
+fn relative_path(cwd: &Path, path: &Path) -> Option<PathBuf> {
+    match path.strip_prefix(cwd) {
+        Ok(relative) => Some(relative.to_path_buf()),
+        // either may be reached through a symbolic link
+        Err(_) => {
+            let cwd = cwd.canonicalize().ok()?;
+            let path = path.canonicalize().ok()?;
+            path.strip_prefix(cwd).ok().map(Path::to_path_buf)
+        }
+    }
+}
+
+fn range(start_line: u64, end_character: usize) -> Value {
+    serde_json::json!({
+        "start": {"line": start_line, "character": 0},
+        "end": {"line": start_line, "character": end_character},
+    })
+}
+
+// The length of the first line, in the UTF-16 code units positions are
+// counted in by default.
+fn first_line_len(contents: &[u8]) -> usize {
+    let first_line = contents.split(|b| *b == b'\n').next().unwrap_or_default();
+    let first_line = first_line.strip_suffix(b"\r").unwrap_or(first_line);
+    String::from_utf8_lossy(first_line).encode_utf16().count()
+}
+
+/// The file URI of the absolute path, percent encoding the bytes that are
+/// not allowed in a URI path.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    let path = cleanse_components(path);
+    let bytes = path_to_bytes(&path);
+    if !bytes.starts_with(b"/") {
+        // Windows paths start with their drive, e.g. file:///C:/
+        uri.push('/');
+    }
+    for byte in bytes.iter() {
+        match byte {
+            b'\\' => uri.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(*byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The path of a file URI, decoding its percent encoded bytes. None when it
+/// is not a file URI.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // skip the host, which is empty or localhost for local files
+    let path = &rest[rest.find('/')?..];
+
+    let mut bytes: Vec<u8> = vec![];
+    let mut encoded = path.bytes();
+    while let Some(byte) = encoded.next() {
+        match byte {
+            b'%' => {
+                let hex = [encoded.next()?, encoded.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    // Windows paths start with their drive rather than a slash
+    if cfg!(windows) && bytes.get(2) == Some(&b':') {
+        bytes.remove(0);
+    }
+    Some(path_from_bytes(bytes))
+}
+
+// Remove the ./ of paths joined onto the workspace root.
+fn cleanse_components(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{
+        first_line_len, goto_targets, path_to_uri, serve, uri_to_path, SERVER_NOT_INITIALIZED,
+    };
+    use alt::config::PartialConfig;
+    use alt::path::kind::FileKind;
+    use alt::path::scoring::ScoredPath;
+    use alt::rpc::read_message;
+    use alt::session::Session;
+    use serde_json;
+    use serde_json::Value;
+    use std::path::{Path, PathBuf};
+
+    fn frame(message: Value) -> Vec<u8> {
+        let body = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+    }
+
+    fn messages(output: &[u8]) -> Vec<Value> {
+        let mut reader = output;
+        let mut messages = vec![];
+        while let Some(body) = read_message(&mut reader).unwrap() {
+            messages.push(serde_json::from_slice(&body).unwrap());
+        }
+        messages
+    }
+
+    #[test]
+    fn goto_targets_are_the_best_alternate_of_each_other_kind() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (3.0, PathBuf::from("app/models/user.rb")),
+            (2.0, PathBuf::from("spec/models/account_spec.rb")),
+            (1.5, PathBuf::from("lib/user.rb")),
+            (1.0, PathBuf::from("spec/factories/users.rb")),
+        ];
+
+        assert_eq!(
+            goto_targets(Path::new("spec/models/user_spec.rb"), &scored_paths),
+            vec![
+                (FileKind::Implementation, Path::new("app/models/user.rb")),
+                (FileKind::Fixture, Path::new("spec/factories/users.rb")),
+            ]
+        );
+    }
+
+    #[test]
+    fn goto_targets_start_with_the_opposite_kind() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (3.0, PathBuf::from("spec/factories/users.rb")),
+            (2.0, PathBuf::from("spec/models/user_spec.rb")),
+        ];
+
+        assert_eq!(
+            goto_targets(Path::new("app/models/user.rb"), &scored_paths),
+            vec![
+                (FileKind::Test, Path::new("spec/models/user_spec.rb")),
+                (FileKind::Fixture, Path::new("spec/factories/users.rb")),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn uris_round_trip_through_paths() {
+        let path = Path::new("/home/me/my project/caf\u{e9}#1.rs");
+        let uri = path_to_uri(path);
+
+        assert_eq!(uri, "file:///home/me/my%20project/caf%C3%A9%231.rs");
+        assert_eq!(uri_to_path(&uri), Some(path.to_path_buf()));
+        assert_eq!(
+            uri_to_path("file://localhost/src/a.rs"),
+            Some(PathBuf::from("/src/a.rs"))
+        );
+        assert_eq!(uri_to_path("untitled:Untitled-1"), None);
+        assert_eq!(
+            path_to_uri(&Path::new("/repo").join("./src/a.rs")),
+            "file:///repo/src/a.rs"
+        );
+    }
+
+    #[test]
+    fn first_line_len_counts_utf16_code_units() {
+        assert_eq!(first_line_len(b"# caf\xc3\xa9 \xf0\x9f\x8e\x89\r\nmore"), 9);
+        assert_eq!(first_line_len(b""), 0);
+    }
+
+    #[test]
+    fn serve_initializes_shuts_down_and_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let input: Vec<u8> = [
+            serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "textDocument/codeLens"}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "initialize", "params": {
+                "rootUri": path_to_uri(dir.path()), "capabilities": {}
+            }}),
+            serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "workspace/executeCommand", "params": {
+                "command": "alt.open", "arguments": ["file:///src/a.rs"]
+            }}),
+            serde_json::json!({"jsonrpc": "2.0", "id": 4, "method": "shutdown"}),
+            serde_json::json!({"jsonrpc": "2.0", "method": "exit"}),
+        ]
+        .iter()
+        .flat_map(|message| frame(message.clone()))
+        .collect();
+        let mut output: Vec<u8> = vec![];
+        let mut root: Option<PathBuf> = None;
+
+        let shut_down = serve(&mut &input[..], &mut output, |workspace_root| {
+            root = workspace_root;
+            Ok(Session::with_user_config(dir.path(), None, PartialConfig::default()).unwrap())
+        })
+        .unwrap();
+
+        assert!(shut_down);
+        assert_eq!(root, Some(dir.path().to_path_buf()));
+        let responses = messages(&output);
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["error"]["code"], SERVER_NOT_INITIALIZED);
+        assert_eq!(
+            responses[1]["result"]["capabilities"]["executeCommandProvider"]["commands"],
+            serde_json::json!(["alt.alternates", "alt.open"])
+        );
+        // the client did not say it can show documents
+        assert_eq!(responses[2]["id"], 3);
+        assert!(responses[2]["error"].is_object());
+        assert_eq!(responses[3]["result"], Value::Null);
+    }
+}
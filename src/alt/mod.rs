@@ -1,193 +1,355 @@
-use alt::path::scoring::{score_paths, ScoredPath};
-use std::cmp::Ordering;
-use std::thread;
-
+use alt::path::scoring::{score_candidate, score_path, Candidate, PathParts, ScoredPath, Scorer};
+use alt::ranking::Ranking;
+use alt::rules::{project, Rule};
+use ignore;
+use ignore::{
+    DirEntry, ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkParallel, WalkState,
+};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::fmt;
+use std::mem;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod git;
+pub mod index;
+pub mod interactive;
+pub mod lsp;
+pub mod output;
 pub mod path;
+pub mod profiles;
+pub mod ranking;
+pub mod rpc;
+pub mod rules;
+pub mod session;
+
+/// The walk of the possible alternates below `root`, following symbolic links
+/// and honouring ignore files.
+pub fn walk_builder(root: &Path, ignore_hidden: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder.follow_links(true).hidden(ignore_hidden);
+    builder
+}
 
-pub fn find_alt(
-    cleansed_path: &str,
-    paths: Vec<String>,
+/// Rank the possible alternate paths for the cleansed path. The paths are
+/// scored as they are produced, so they can be streamed, e.g. from a
+/// directory walk, without first being collected.
+pub fn find_alt<S, C, I>(
+    cleansed_path: &Path,
+    paths: I,
     truncate_len: usize,
-    leading_edge_filename_weight: f32,
-    filename_weight: f32,
-    path_weight: f32,
-) -> Vec<ScoredPath> {
-    let mut possible_paths_with_scores: Vec<ScoredPath> = score_paths(
-        paths,
-        cleansed_path,
-        leading_edge_filename_weight,
-        filename_weight,
-        path_weight,
-    );
-
-    possible_paths_with_scores.sort_by(order_scored_paths);
-
-    truncate_scored_paths(&mut possible_paths_with_scores, truncate_len);
-
-    possible_paths_with_scores
+    rules: &[Rule],
+    scorer: &S,
+) -> Vec<ScoredPath>
+where
+    S: Scorer + ?Sized,
+    C: Candidate,
+    I: IntoIterator<Item = C>,
+{
+    let mut ranking = Ranking::new(truncate_len, project_path(rules, cleansed_path));
+    let query_parts = PathParts::new(cleansed_path);
+
+    for (index, path) in paths.into_iter().enumerate() {
+        if let Some(scored_path) = score_candidate(&path, cleansed_path, &query_parts, scorer) {
+            ranking.push(index, scored_path);
+        }
+    }
+
+    ranking.into_scored_paths()
 }
 
 #[derive(Debug)]
 pub enum FindAltWithThreadsError {
-    NoAvailableParallelism,
+    ThreadPool(ThreadPoolBuildError),
+    /// A thread panicked while scoring, with the message it panicked with.
+    Panicked(String),
 }
 
-pub fn find_alt_with_threads(
-    cleansed_path: &str,
-    paths: Vec<String>,
-    truncate_len: usize,
-    leading_edge_filename_weight: f32,
-    filename_weight: f32,
-    path_weight: f32,
-) -> Result<Vec<ScoredPath>, FindAltWithThreadsError> {
-    if paths.is_empty() {
-        return Ok(vec![]);
-    }
-
-    // get the parallel potential
-    let parallel_est = thread::available_parallelism()
-        .map_err(|_| FindAltWithThreadsError::NoAvailableParallelism)?;
-
-    // split the paths vec into that many groups
-    let chunk_size = (paths.len() / parallel_est) + (paths.len() % parallel_est);
-    let mut thread_handles: Vec<std::thread::JoinHandle<Vec<ScoredPath>>> = Vec::new();
-
-    // spin up thread for each group to score the paths
-    for chunk in paths.chunks(chunk_size) {
-        let threads_paths: Vec<String> = chunk.to_vec();
-        let threads_cleansed_path: String = cleansed_path.to_owned();
-        let threads_leading_edge_filename_weight: f32 = leading_edge_filename_weight;
-        let threads_filename_weight: f32 = filename_weight;
-        let threads_path_weight: f32 = path_weight;
-        let thread_handle = std::thread::spawn(move || {
-            score_paths(
-                threads_paths,
-                &threads_cleansed_path,
-                threads_leading_edge_filename_weight,
-                threads_filename_weight,
-                threads_path_weight,
-            )
-        });
-        thread_handles.push(thread_handle);
+impl fmt::Display for FindAltWithThreadsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FindAltWithThreadsError::ThreadPool(e) => {
+                write!(f, "Failure occurred starting threads, {}", e)
+            }
+            FindAltWithThreadsError::Panicked(message) => {
+                write!(f, "Failure occurred scoring paths in a thread, {}", message)
+            }
+        }
     }
+}
 
-    // join on all the threads and get back the scored paths
-    // combine them all back into a single vec
-    let collections_of_scored_paths: Vec<Vec<ScoredPath>> = thread_handles
-        .into_iter()
-        .map(|h| h.join().unwrap())
-        .collect();
+/// Build a pool of `threads` threads, or of one thread per CPU when `threads`
+/// is 0, to score paths on with `find_alt_with_threads`. The pool can be
+/// reused across calls.
+pub fn thread_pool(threads: usize) -> Result<ThreadPool, FindAltWithThreadsError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(FindAltWithThreadsError::ThreadPool)
+}
 
-    let mut scored_paths = collections_of_scored_paths.concat();
+// Run the given function, turning a panic in it, or in any thread it waits
+// on, into an error.
+fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, FindAltWithThreadsError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown panic".to_string(),
+            },
+        };
+        FindAltWithThreadsError::Panicked(message)
+    })
+}
 
-    scored_paths.sort_by(order_scored_paths);
+// The number of paths scored as one job.
+const BATCH_SIZE: usize = 256;
 
-    truncate_scored_paths(&mut scored_paths, truncate_len);
+/// Rank the possible alternate paths for the cleansed path like `find_alt`,
+/// scoring batches of them on the threads of the pool while they are still
+/// being produced on the calling thread. Idle threads steal batches from busy
+/// ones, and the paths are only borrowed if given by reference.
+pub fn find_alt_with_threads<S, P, I>(
+    cleansed_path: &Path,
+    paths: I,
+    truncate_len: usize,
+    rules: &[Rule],
+    scorer: &S,
+    pool: &ThreadPool,
+) -> Result<Vec<ScoredPath>, FindAltWithThreadsError>
+where
+    S: Scorer + Sync + ?Sized,
+    P: Candidate + Send,
+    I: IntoIterator<Item = P>,
+{
+    let projections = project_path(rules, cleansed_path);
+
+    // a ranking per thread of the pool, and one for the calling thread
+    let caller = pool.current_num_threads();
+    let rankings: Vec<Mutex<Ranking>> = (0..caller + 1)
+        .map(|_| Mutex::new(Ranking::new(truncate_len, projections.clone())))
+        .collect();
+    let query_parts = PathParts::new(cleansed_path);
+    let query = (cleansed_path, &query_parts);
+    let pending = AtomicUsize::new(0);
+    let max_pending = pool.current_num_threads() * 2;
+
+    catch_panic(|| {
+        pool.in_place_scope(|scope| {
+            let mut offset = 0;
+            let mut batch: Vec<P> = Vec::with_capacity(BATCH_SIZE);
+            for path in paths {
+                batch.push(path);
+                if batch.len() < BATCH_SIZE {
+                    continue;
+                }
+                let full_batch = mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                // when the threads are behind, score the batch here rather
+                // than queueing up every path in memory
+                if pending.load(AtomicOrdering::Acquire) >= max_pending {
+                    score_batch(offset, full_batch, query, scorer, &rankings[caller]);
+                } else {
+                    pending.fetch_add(1, AtomicOrdering::AcqRel);
+                    let (rankings, pending) = (&rankings, &pending);
+                    scope.spawn(move |_| {
+                        let slot = pool.current_thread_index().unwrap_or(caller);
+                        score_batch(offset, full_batch, query, scorer, &rankings[slot]);
+                        pending.fetch_sub(1, AtomicOrdering::AcqRel);
+                    });
+                }
+                offset += BATCH_SIZE;
+            }
+            score_batch(offset, batch, query, scorer, &rankings[caller]);
+        })
+    })?;
+
+    // merge the top paths of every thread
+    let mut ranking = Ranking::new(truncate_len, projections);
+    for threads_ranking in rankings {
+        ranking.merge(threads_ranking.into_inner().unwrap());
+    }
 
-    Ok(scored_paths)
+    Ok(ranking.into_scored_paths())
 }
 
-fn order_scored_paths(scored_path_a: &ScoredPath, scored_path_b: &ScoredPath) -> Ordering {
-    if scored_path_a.0 > scored_path_b.0 {
-        Ordering::Less
-    } else if scored_path_a.0 < scored_path_b.0 {
-        Ordering::Greater
-    } else {
-        Ordering::Equal
+// Score a batch of consecutive paths, the first of which is at `offset` in
+// the input, into the ranking of the thread scoring them.
+fn score_batch<S: Scorer + Sync + ?Sized, P: Candidate>(
+    offset: usize,
+    batch: Vec<P>,
+    (cleansed_path, query_parts): (&Path, &PathParts),
+    scorer: &S,
+    ranking: &Mutex<Ranking>,
+) {
+    let mut ranking = ranking.lock().unwrap();
+    for (index, path) in batch.iter().enumerate() {
+        if let Some(scored_path) = score_candidate(path, cleansed_path, query_parts, scorer) {
+            ranking.push(offset + index, scored_path);
+        }
     }
 }
 
-fn truncate_scored_paths(scored_paths: &mut Vec<ScoredPath>, len: usize) {
-    match len {
-        0 => (),
-        _ => scored_paths.truncate(len),
+/// Rank the possible alternate paths found by walking directories in
+/// parallel, keeping only the files for which `keep` is true. Each walking
+/// thread scores the files it finds, and as they are found in no particular
+/// order ties are ranked by path, which is the order a walk sorted by file
+/// name would find them in.
+pub fn find_alt_with_parallel_walk<S, F>(
+    cleansed_path: &Path,
+    walker: WalkParallel,
+    keep: &F,
+    truncate_len: usize,
+    rules: &[Rule],
+    scorer: &S,
+) -> Result<Vec<ScoredPath>, FindAltWithThreadsError>
+where
+    S: Scorer + Sync,
+    F: Fn(&Path) -> bool + Sync,
+{
+    let projections = project_path(rules, cleansed_path);
+    let rankings: Mutex<Vec<Ranking>> = Mutex::new(vec![]);
+
+    catch_panic(|| {
+        walker.visit(&mut WalkScorerBuilder {
+            cleansed_path,
+            keep,
+            scorer,
+            truncate_len,
+            projections: &projections,
+            rankings: &rankings,
+        })
+    })?;
+
+    let mut ranking = Ranking::new(truncate_len, projections.clone());
+    for threads_ranking in rankings.into_inner().unwrap() {
+        ranking.merge(threads_ranking);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{
-        find_alt, find_alt_with_threads, order_scored_paths, truncate_scored_paths, ScoredPath,
-    };
-
-    #[test]
-    fn truncate_scored_paths_with_zero_len() {
-        let mut scored_paths: Vec<ScoredPath> = vec![
-            (0.8, "some/path/to/a/file.ts".to_owned()),
-            (0.4, "some/path/to/another/foo.ts".to_owned()),
-            (0.2, "some/other_path/to/a/bar.ts".to_owned()),
-            (0.1, "some/short/path/zoo.ts".to_owned()),
-            (0.023, "some/blue/fortytwo/sports_ball.ts".to_owned()),
-        ];
+    Ok(ranking.into_scored_paths())
+}
 
-        truncate_scored_paths(&mut scored_paths, 0);
+struct WalkScorerBuilder<'s, S: 's, F: 's> {
+    cleansed_path: &'s Path,
+    keep: &'s F,
+    scorer: &'s S,
+    truncate_len: usize,
+    projections: &'s [PathBuf],
+    rankings: &'s Mutex<Vec<Ranking>>,
+}
 
-        assert_eq!(scored_paths.len(), 5);
+impl<'s, S: Scorer + Sync, F: Fn(&Path) -> bool + Sync> ParallelVisitorBuilder<'s>
+    for WalkScorerBuilder<'s, S, F>
+{
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(WalkScorer {
+            cleansed_path: self.cleansed_path,
+            keep: self.keep,
+            scorer: self.scorer,
+            ranking: Some(Ranking::new(self.truncate_len, self.projections.to_vec())),
+            rankings: self.rankings,
+        })
     }
+}
 
-    #[test]
-    fn truncate_scored_paths_with_non_zero_len() {
-        let mut scored_paths: Vec<ScoredPath> = vec![
-            (0.8, "some/path/to/a/file.ts".to_owned()),
-            (0.4, "some/path/to/another/foo.ts".to_owned()),
-            (0.2, "some/other_path/to/a/bar.ts".to_owned()),
-            (0.1, "some/short/path/zoo.ts".to_owned()),
-            (0.023, "some/blue/fortytwo/sports_ball.ts".to_owned()),
-        ];
-
-        truncate_scored_paths(&mut scored_paths, 3);
+// Scores the files found by one walking thread, handing its ranking over to
+// be merged when the walk is done.
+struct WalkScorer<'s, S: 's, F: 's> {
+    cleansed_path: &'s Path,
+    keep: &'s F,
+    scorer: &'s S,
+    ranking: Option<Ranking>,
+    rankings: &'s Mutex<Vec<Ranking>>,
+}
 
-        assert_eq!(scored_paths.len(), 3);
+impl<'s, S: Scorer + Sync, F: Fn(&Path) -> bool + Sync> ParallelVisitor for WalkScorer<'s, S, F> {
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => return WalkState::Continue,
+        };
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+            || !(self.keep)(entry.path())
+        {
+            return WalkState::Continue;
+        }
+        if let (Some(ranking), Some(scored_path)) = (
+            self.ranking.as_mut(),
+            score_path(entry.path(), self.cleansed_path, self.scorer),
+        ) {
+            ranking.push(0, scored_path);
+        }
+        WalkState::Continue
     }
+}
 
-    #[test]
-    fn order_scored_paths_with_a_larger() {
-        let ordering = order_scored_paths(
-            &(0.3, "some/path/to/a/file.ts".to_owned()),
-            &(0.2, "some/other/path/bar.ts".to_owned()),
-        );
-
-        assert_eq!(ordering, std::cmp::Ordering::Less);
+impl<'s, S, F> Drop for WalkScorer<'s, S, F> {
+    fn drop(&mut self) {
+        if let Some(ranking) = self.ranking.take() {
+            if let Ok(mut rankings) = self.rankings.lock() {
+                rankings.push(ranking);
+            }
+        }
     }
+}
 
-    #[test]
-    fn order_scored_paths_with_a_smaller() {
-        let ordering = order_scored_paths(
-            &(0.2, "some/path/to/a/file.ts".to_owned()),
-            &(0.3, "some/other/path/bar.ts".to_owned()),
-        );
-
-        assert_eq!(ordering, std::cmp::Ordering::Greater);
-    }
+// Rules match on text, so paths that are not valid UTF-8 have no projections.
+fn project_path(rules: &[Rule], path: &Path) -> Vec<PathBuf> {
+    path.to_str()
+        .map(|path| project(rules, path))
+        .unwrap_or_default()
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+}
 
-    #[test]
-    fn order_scored_paths_with_a_and_b_equal() {
-        let ordering = order_scored_paths(
-            &(0.3, "some/path/to/a/file.ts".to_owned()),
-            &(0.3, "some/other/path/bar.ts".to_owned()),
-        );
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
 
-        assert_eq!(ordering, std::cmp::Ordering::Equal);
-    }
+    use super::{
+        find_alt, find_alt_with_parallel_walk, find_alt_with_threads, thread_pool,
+        FindAltWithThreadsError, Rule, ScoredPath, Scorer,
+    };
+    use alt::path::scoring::SimilarityScorer;
+    use ignore::WalkBuilder;
+    use std::fs;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn find_alt_scores_paths_and_sorts_them_by_score() {
-        let paths: Vec<String> = vec![
-            "src/database/nft-wallet/nft-wallet.repository.spec.ts".to_owned(),
-            "src/models/mocks/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/nft-wallet.ts".to_owned(),
-            "src/database/nft-wallet/nft-wallet.repository.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "src/database/nft-wallet/nft-wallet.repository.spec.ts".into(),
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+            "src/database/nft-wallet/nft-wallet.repository.ts".into(),
         ];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt("src/models/nft-wallet.ts", paths, 0, 100.0, 10.0, 1.0);
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &SimilarityScorer::new(100.0, 10.0, 1.0),
+        );
         assert_eq!(scored_paths.len(), 5);
         assert!(scored_paths[0].0 > scored_paths[1].0);
         assert!(scored_paths[1].0 > scored_paths[2].0);
         assert!(scored_paths[2].0 > scored_paths[3].0);
         assert!(scored_paths[3].0 > scored_paths[4].0);
 
-        let stripped_scored_paths: Vec<String> = scored_paths.into_iter().map(|s| s.1).collect();
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
         assert_eq!(
             stripped_scored_paths,
             vec![
@@ -202,19 +364,27 @@ mod tests {
 
     #[test]
     fn find_alt_scores_paths_and_drops_full_matches() {
-        let paths: Vec<String> = vec![
-            "src/models/nft-wallet.ts".to_owned(), // should be dropped
-            "src/database/nft-wallet/nft-wallet.repository.spec.ts".to_owned(),
-            "src/models/mocks/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/nft-wallet.ts".to_owned(),
-            "src/database/nft-wallet/nft-wallet.repository.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "src/models/nft-wallet.ts".into(), // should be dropped
+            "src/database/nft-wallet/nft-wallet.repository.spec.ts".into(),
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+            "src/database/nft-wallet/nft-wallet.repository.ts".into(),
         ];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt("src/models/nft-wallet.ts", paths, 0, 100.0, 10.0, 1.0);
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &SimilarityScorer::new(100.0, 10.0, 1.0),
+        );
         assert_eq!(scored_paths.len(), 5);
 
-        let stripped_scored_paths: Vec<String> = scored_paths.into_iter().map(|s| s.1).collect();
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
         assert_eq!(
             stripped_scored_paths,
             vec![
@@ -229,20 +399,28 @@ mod tests {
 
     #[test]
     fn find_alt_truncates_when_truncate_len_is_greater_than_zero() {
-        let paths: Vec<String> = vec![
-            "src/database/nft-wallet/nft-wallet.repository.spec.ts".to_owned(),
-            "src/models/mocks/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/nft-wallet.ts".to_owned(),
-            "src/database/nft-wallet/nft-wallet.repository.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "src/database/nft-wallet/nft-wallet.repository.spec.ts".into(),
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+            "src/database/nft-wallet/nft-wallet.repository.ts".into(),
         ];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt("src/models/nft-wallet.ts", paths, 3, 100.0, 10.0, 1.0);
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            3,
+            &[],
+            &SimilarityScorer::new(100.0, 10.0, 1.0),
+        );
         assert_eq!(scored_paths.len(), 3);
         assert!(scored_paths[0].0 > scored_paths[1].0);
         assert!(scored_paths[1].0 > scored_paths[2].0);
 
-        let stripped_scored_paths: Vec<String> = scored_paths.into_iter().map(|s| s.1).collect();
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
         assert_eq!(
             stripped_scored_paths,
             vec![
@@ -255,22 +433,30 @@ mod tests {
 
     #[test]
     fn find_alt_does_not_truncate_results_when_truncate_len_is_zero() {
-        let paths: Vec<String> = vec![
-            "src/database/nft-wallet/nft-wallet.repository.spec.ts".to_owned(),
-            "src/models/mocks/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/nft-wallet.ts".to_owned(),
-            "src/database/nft-wallet/nft-wallet.repository.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "src/database/nft-wallet/nft-wallet.repository.spec.ts".into(),
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+            "src/database/nft-wallet/nft-wallet.repository.ts".into(),
         ];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt("src/models/nft-wallet.ts", paths, 0, 100.0, 10.0, 1.0);
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &SimilarityScorer::new(100.0, 10.0, 1.0),
+        );
         assert_eq!(scored_paths.len(), 5);
         assert!(scored_paths[0].0 > scored_paths[1].0);
         assert!(scored_paths[1].0 > scored_paths[2].0);
         assert!(scored_paths[2].0 > scored_paths[3].0);
         assert!(scored_paths[3].0 > scored_paths[4].0);
 
-        let stripped_scored_paths: Vec<String> = scored_paths.into_iter().map(|s| s.1).collect();
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
         assert_eq!(
             stripped_scored_paths,
             vec![
@@ -285,18 +471,26 @@ mod tests {
 
     #[test]
     fn find_alt_with_larger_path_weight() {
-        let paths: Vec<String> = vec![
-            "src/database/nft-wallet/nft-wallet.repository.spec.ts".to_owned(),
-            "src/models/mocks/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".to_owned(),
-            "src/concerns/nft/models/nft-wallet.ts".to_owned(),
-            "src/database/nft-wallet/nft-wallet.repository.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "src/database/nft-wallet/nft-wallet.repository.spec.ts".into(),
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/mockes/nft-wallet.mocks.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+            "src/database/nft-wallet/nft-wallet.repository.ts".into(),
         ];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt("src/models/nft-wallet.ts", paths, 0, 1.0, 1.0, 10.0);
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &SimilarityScorer::new(1.0, 1.0, 10.0),
+        );
         assert_eq!(scored_paths.len(), 5);
 
-        let stripped_scored_paths: Vec<String> = scored_paths.into_iter().map(|s| s.1).collect();
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
         assert_eq!(
             stripped_scored_paths,
             vec![
@@ -311,18 +505,335 @@ mod tests {
 
     #[test]
     fn find_alt_with_no_paths() {
-        let paths: Vec<String> = vec![];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt("src/models/nft-wallet.ts", paths, 0, 1.0, 1.0, 10.0);
+        let paths: Vec<PathBuf> = vec![];
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &SimilarityScorer::new(1.0, 1.0, 10.0),
+        );
         assert_eq!(scored_paths.len(), 0);
     }
 
     #[test]
     fn find_alt_with_threads_with_no_paths() {
-        let paths: Vec<String> = vec![];
-        let scored_paths: Vec<ScoredPath> =
-            find_alt_with_threads("src/models/nft-wallet.ts", paths, 0, 1.0, 1.0, 10.0)
-                .expect("Failed to find parallelism");
+        let paths: Vec<PathBuf> = vec![];
+        let scored_paths: Vec<ScoredPath> = find_alt_with_threads(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &SimilarityScorer::new(1.0, 1.0, 10.0),
+            &thread_pool(2).unwrap(),
+        )
+        .expect("Failed to find parallelism");
         assert_eq!(scored_paths.len(), 0);
     }
+
+    #[derive(Clone)]
+    struct ShortestPathScorer;
+
+    impl Scorer for ShortestPathScorer {
+        fn score(&self, _query_path: &Path, candidate_path: &Path) -> f32 {
+            1.0 / candidate_path.as_os_str().len() as f32
+        }
+    }
+
+    #[test]
+    fn find_alt_with_custom_scorer() {
+        let paths: Vec<PathBuf> = vec![
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/a.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+        ];
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &ShortestPathScorer,
+        );
+
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            stripped_scored_paths,
+            vec![
+                "src/a.ts",
+                "src/models/mocks/nft-wallet.mocks.ts",
+                "src/concerns/nft/models/nft-wallet.ts",
+            ]
+        )
+    }
+
+    #[test]
+    fn find_alt_with_threads_with_custom_scorer() {
+        let paths: Vec<PathBuf> = vec![
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/a.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+        ];
+        let scored_paths: Vec<ScoredPath> = find_alt_with_threads(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            1,
+            &[],
+            &ShortestPathScorer,
+            &thread_pool(2).unwrap(),
+        )
+        .expect("Failed to find parallelism");
+
+        assert_eq!(scored_paths.len(), 1);
+        assert_eq!(scored_paths[0].1, Path::new("src/a.ts"));
+    }
+
+    #[test]
+    fn find_alt_ranks_rule_projections_first() {
+        let paths: Vec<PathBuf> = vec![
+            "spec/models/user_spec.rb".into(),
+            "spec/models/user_fee_spec.rb".into(),
+            "lib/models/user.rb".into(),
+            "app/models/user.rb".into(),
+        ];
+        let rules = vec![
+            Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap(),
+            Rule::new("spec/**/*_spec.rb", "lib/{1}/{2}.rb").unwrap(),
+        ];
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("spec/models/user_spec.rb"),
+            paths,
+            0,
+            &rules,
+            &ShortestPathScorer,
+        );
+
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            stripped_scored_paths,
+            vec![
+                "app/models/user.rb",
+                "lib/models/user.rb",
+                "spec/models/user_fee_spec.rb",
+            ]
+        )
+    }
+
+    #[test]
+    fn find_alt_falls_back_to_scoring_when_no_rule_projection_exists() {
+        let paths: Vec<PathBuf> = vec![
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/a.ts".into(),
+        ];
+        let rules = vec![Rule::new("src/**/*.ts", "test/{1}/{2}.test.ts").unwrap()];
+        let scored_paths: Vec<ScoredPath> = find_alt(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &rules,
+            &ShortestPathScorer,
+        );
+
+        let stripped_scored_paths: Vec<String> = scored_paths
+            .into_iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            stripped_scored_paths,
+            vec!["src/a.ts", "src/models/mocks/nft-wallet.mocks.ts"]
+        )
+    }
+
+    #[test]
+    fn find_alt_with_threads_ranks_rule_projections_first() {
+        let paths: Vec<PathBuf> = vec!["src/a.ts".into(), "test/models/nft-wallet.test.ts".into()];
+        let rules = vec![Rule::new("src/**/*.ts", "test/{1}/{2}.test.ts").unwrap()];
+        let scored_paths: Vec<ScoredPath> = find_alt_with_threads(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            1,
+            &rules,
+            &ShortestPathScorer,
+            &thread_pool(2).unwrap(),
+        )
+        .expect("Failed to find parallelism");
+
+        assert_eq!(scored_paths.len(), 1);
+        assert_eq!(
+            scored_paths[0].1,
+            Path::new("test/models/nft-wallet.test.ts")
+        );
+    }
+
+    #[test]
+    fn find_alt_top_paths_match_the_truncated_full_ranking() {
+        let paths: Vec<PathBuf> = vec![
+            "src/b.ts".into(),
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/a.ts".into(),
+            "test/models/nft-wallet.test.ts".into(),
+            "src/c.ts".into(),
+            "src/concerns/nft/models/nft-wallet.ts".into(),
+            "src/d.ts".into(),
+        ];
+        let rules = vec![Rule::new("src/**/*.ts", "test/{1}/{2}.test.ts").unwrap()];
+        let query_path = Path::new("src/models/nft-wallet.ts");
+        let full = find_alt(query_path, paths.clone(), 0, &rules, &ShortestPathScorer);
+
+        for truncate_len in 1..paths.len() + 1 {
+            let top = find_alt(
+                query_path,
+                paths.clone(),
+                truncate_len,
+                &rules,
+                &ShortestPathScorer,
+            );
+            let threaded_top = find_alt_with_threads(
+                query_path,
+                paths.clone(),
+                truncate_len,
+                &rules,
+                &ShortestPathScorer,
+                &thread_pool(2).unwrap(),
+            )
+            .expect("Failed to find parallelism");
+
+            let expected: Vec<ScoredPath> = full.iter().take(truncate_len).cloned().collect();
+            assert_eq!(top, expected);
+            assert_eq!(threaded_top, expected);
+        }
+        let ties: Vec<String> = full[1..5]
+            .iter()
+            .map(|s| s.1.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(ties, vec!["src/b.ts", "src/a.ts", "src/c.ts", "src/d.ts"]);
+    }
+
+    #[test]
+    fn find_alt_with_threads_ranks_streamed_paths_like_find_alt() {
+        let paths =
+            (0..1000).map(|i| PathBuf::from(format!("src/{}/nft-wallet{}.ts", i % 7, i % 13)));
+        let query_path = Path::new("src/models/nft-wallet.ts");
+        let scorer = SimilarityScorer::new(100.0, 10.0, 1.0);
+
+        for truncate_len in [0, 1, 10] {
+            let expected = find_alt(query_path, paths.clone(), truncate_len, &[], &scorer);
+            let scored_paths = find_alt_with_threads(
+                query_path,
+                paths.clone(),
+                truncate_len,
+                &[],
+                &scorer,
+                &thread_pool(2).unwrap(),
+            )
+            .expect("Failed to find parallelism");
+            assert_eq!(scored_paths, expected);
+        }
+    }
+
+    #[test]
+    fn find_alt_with_parallel_walk_ranks_like_a_sorted_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "src/b.ts",
+            "src/a.ts",
+            "src/a/c.ts",
+            "src/models/nft-wallet.ts",
+            "test/models/nft-wallet.test.ts",
+            "lib/zz.ts",
+            "lib/aa.ts",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let query_path = dir.path().join("src/models/nft-wallet.ts");
+        let keep = |path: &Path| !path.ends_with("lib/zz.ts");
+
+        let walked: Vec<PathBuf> = WalkBuilder::new(dir.path())
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .map(|entry| entry.into_path())
+            .filter(|path| keep(path))
+            .collect();
+
+        for truncate_len in [0, 1, 3] {
+            let expected = find_alt(
+                &query_path,
+                walked.clone(),
+                truncate_len,
+                &[],
+                &ShortestPathScorer,
+            );
+            let scored_paths = find_alt_with_parallel_walk(
+                &query_path,
+                WalkBuilder::new(dir.path()).threads(4).build_parallel(),
+                &keep,
+                truncate_len,
+                &[],
+                &ShortestPathScorer,
+            )
+            .unwrap();
+            assert_eq!(scored_paths, expected);
+        }
+    }
+
+    #[test]
+    fn find_alt_with_threads_borrows_the_paths() {
+        let paths: Vec<PathBuf> = vec![
+            "src/models/mocks/nft-wallet.mocks.ts".into(),
+            "src/a.ts".into(),
+        ];
+        let scored_paths = find_alt_with_threads(
+            Path::new("src/models/nft-wallet.ts"),
+            &paths,
+            0,
+            &[],
+            &ShortestPathScorer,
+            &thread_pool(2).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(scored_paths[0].1, paths[1]);
+        assert_eq!(paths.len(), 2);
+    }
+
+    struct PanickingScorer;
+
+    impl Scorer for PanickingScorer {
+        fn score(&self, _query_path: &Path, candidate_path: &Path) -> f32 {
+            if candidate_path.ends_with("boom.ts") {
+                panic!("boom");
+            }
+            0.0
+        }
+    }
+
+    #[test]
+    fn find_alt_with_threads_returns_an_error_when_a_thread_panics() {
+        let paths = (0..1000).map(|i| match i {
+            700 => PathBuf::from("src/boom.ts"),
+            _ => PathBuf::from(format!("src/{}.ts", i)),
+        });
+        let result = find_alt_with_threads(
+            Path::new("src/models/nft-wallet.ts"),
+            paths,
+            0,
+            &[],
+            &PanickingScorer,
+            &thread_pool(2).unwrap(),
+        );
+
+        match result {
+            Err(FindAltWithThreadsError::Panicked(message)) => assert_eq!(message, "boom"),
+            other => panic!("Expected a panic error, got {:?}", other),
+        }
+    }
 }
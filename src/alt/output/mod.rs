@@ -0,0 +1,660 @@
+use alt::path::kind::classify;
+use alt::path::scoring::{ScoreComponents, ScoredPath, SimilarityScorer};
+use alt::path::utils::path_to_bytes;
+use serde_json;
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The format the ranked alternates are output in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// One path per line.
+    #[default]
+    Plain,
+    /// A single JSON document with the query, the weights and the
+    /// alternates, each with its score and score components.
+    Json,
+    /// One JSON document per alternate, each including the query and the
+    /// weights.
+    Jsonl,
+    /// One `score<TAB>path` record per alternate, for fzf and skim.
+    Fzf,
+    /// One `score<TAB>path<TAB>metadata` record per alternate, the metadata
+    /// being the kind of the alternate and its score components, to be
+    /// shown in the preview window of fzf and skim.
+    FzfPreview,
+    /// One `path:1:1: score=… kind=…` line per alternate, for the quickfix
+    /// and location lists of Vim and Neovim.
+    Quickfix,
+    /// One `path:1:1: info: score=… kind=…` line per alternate, for Emacs'
+    /// compilation mode.
+    Emacs,
+    /// One `path(1,1): info ALT1: score=… kind=…` line per alternate, as
+    /// matched by VS Code's `$msCompile` problem matcher.
+    Vscode,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Format::Plain),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            "fzf" => Ok(Format::Fzf),
+            "fzf-preview" => Ok(Format::FzfPreview),
+            "quickfix" => Ok(Format::Quickfix),
+            "emacs" => Ok(Format::Emacs),
+            "vscode" => Ok(Format::Vscode),
+            _ => Err(format!(
+                "Unknown format {}, expected plain, json, jsonl, fzf, fzf-preview, quickfix, emacs or vscode",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Plain => "plain",
+            Format::Json => "json",
+            Format::Jsonl => "jsonl",
+            Format::Fzf => "fzf",
+            Format::FzfPreview => "fzf-preview",
+            Format::Quickfix => "quickfix",
+            Format::Emacs => "emacs",
+            Format::Vscode => "vscode",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonWeights {
+    leading_edge_filename: f32,
+    filename: f32,
+    path: f32,
+    token_filename: f32,
+    unordered_token_filename: f32,
+}
+
+impl<'a> From<&'a SimilarityScorer> for JsonWeights {
+    fn from(scorer: &'a SimilarityScorer) -> Self {
+        JsonWeights {
+            leading_edge_filename: scorer.leading_edge_filename_weight,
+            filename: scorer.filename_weight,
+            path: scorer.path_weight,
+            token_filename: scorer.token_filename_weight,
+            unordered_token_filename: scorer.unordered_token_filename_weight,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonComponent {
+    similarity: f32,
+    score: f32,
+}
+
+#[derive(Serialize)]
+struct JsonComponents {
+    leading_edge_filename: JsonComponent,
+    filename: JsonComponent,
+    path: JsonComponent,
+    token_filename: JsonComponent,
+    unordered_token_filename: JsonComponent,
+}
+
+impl JsonComponents {
+    fn new(similarities: &ScoreComponents, scores: &ScoreComponents) -> Self {
+        let component = |similarity: f32, score: f32| JsonComponent { similarity, score };
+        JsonComponents {
+            leading_edge_filename: component(
+                similarities.leading_edge_filename,
+                scores.leading_edge_filename,
+            ),
+            filename: component(similarities.filename, scores.filename),
+            path: component(similarities.path, scores.path),
+            token_filename: component(similarities.token_filename, scores.token_filename),
+            unordered_token_filename: component(
+                similarities.unordered_token_filename,
+                scores.unordered_token_filename,
+            ),
+        }
+    }
+}
+
+// JSON strings have to be valid Unicode, so paths that are not valid UTF-8
+// are output lossily along with their raw bytes.
+fn json_path(path: &Path) -> (Cow<'_, str>, Option<Cow<'_, [u8]>>) {
+    match path.to_str() {
+        Some(s) => (Cow::Borrowed(s), None),
+        None => (path.to_string_lossy(), Some(path_to_bytes(path))),
+    }
+}
+
+#[derive(Serialize)]
+struct JsonAlternate<'a> {
+    path: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_bytes: Option<Cow<'a, [u8]>>,
+    score: f32,
+    components: JsonComponents,
+}
+
+impl<'a> JsonAlternate<'a> {
+    fn new(query_path: &Path, scorer: &SimilarityScorer, scored_path: &'a ScoredPath) -> Self {
+        let similarities = scorer.components(query_path, &scored_path.1);
+        let (path, path_bytes) = json_path(&scored_path.1);
+        JsonAlternate {
+            path,
+            path_bytes,
+            score: scored_path.0,
+            components: JsonComponents::new(&similarities, &scorer.weigh(&similarities)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonQuery<'a> {
+    query: Cow<'a, str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query_bytes: Option<Cow<'a, [u8]>>,
+    weights: JsonWeights,
+}
+
+impl<'a> JsonQuery<'a> {
+    fn new(query_path: &'a Path, scorer: &SimilarityScorer) -> Self {
+        let (query, query_bytes) = json_path(query_path);
+        JsonQuery {
+            query,
+            query_bytes,
+            weights: scorer.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    #[serde(flatten)]
+    query: JsonQuery<'a>,
+    alternates: Vec<JsonAlternate<'a>>,
+}
+
+impl<'a> JsonDocument<'a> {
+    fn new(
+        query_path: &'a Path,
+        scorer: &SimilarityScorer,
+        scored_paths: &'a [ScoredPath],
+    ) -> Self {
+        JsonDocument {
+            query: JsonQuery::new(query_path, scorer),
+            alternates: scored_paths
+                .iter()
+                .map(|scored_path| JsonAlternate::new(query_path, scorer, scored_path))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    #[serde(flatten)]
+    query: &'a JsonQuery<'a>,
+    #[serde(flatten)]
+    alternate: JsonAlternate<'a>,
+}
+
+/// Write the ranked alternates for the query path in the given format, the
+/// records of the formats with one per alternate separated by `delimiter`,
+/// e.g. a NUL byte so that paths may contain newlines. The score components
+/// are computed with the scorer the alternates were ranked with.
+pub fn write_alternates<W: Write>(
+    out: &mut W,
+    format: Format,
+    delimiter: u8,
+    query_path: &Path,
+    scorer: &SimilarityScorer,
+    scored_paths: &[ScoredPath],
+) -> io::Result<()> {
+    match format {
+        Format::Plain => out.write_all(&scored_paths_to_bytes(scored_paths, delimiter)),
+        Format::Json => {
+            let document = JsonDocument::new(query_path, scorer, scored_paths);
+            serde_json::to_writer(&mut *out, &document)?;
+            out.write_all(b"\n")
+        }
+        Format::Jsonl => {
+            let query = JsonQuery::new(query_path, scorer);
+            for scored_path in scored_paths.iter() {
+                let line = JsonLine {
+                    query: &query,
+                    alternate: JsonAlternate::new(query_path, scorer, scored_path),
+                };
+                serde_json::to_writer(&mut *out, &line)?;
+                out.write_all(&[delimiter])?;
+            }
+            Ok(())
+        }
+        Format::Fzf => {
+            for (score, path) in scored_paths.iter() {
+                write!(out, "{:.2}\t", score)?;
+                out.write_all(&path_to_bytes(path))?;
+                out.write_all(&[delimiter])?;
+            }
+            Ok(())
+        }
+        Format::FzfPreview => {
+            for scored_path in scored_paths.iter() {
+                write!(out, "{:.2}\t", scored_path.0)?;
+                out.write_all(&path_to_bytes(&scored_path.1))?;
+                write!(out, "\t{}", metadata(query_path, scorer, scored_path))?;
+                out.write_all(&[delimiter])?;
+            }
+            Ok(())
+        }
+        // editors jump to the first line and column of the alternates
+        Format::Quickfix => write_locations(out, delimiter, scored_paths, ":1:1: "),
+        Format::Emacs => write_locations(out, delimiter, scored_paths, ":1:1: info: "),
+        Format::Vscode => write_locations(out, delimiter, scored_paths, "(1,1): info ALT1: "),
+    }
+}
+
+/// Write the ranked alternates of each of many query paths, in turn. The
+/// plain format puts each query's alternates under a `==> query <==` header,
+/// like head(1) does with many files, and json outputs an array with the
+/// document json outputs for each query, in the order of the queries, so that
+/// queries that are not valid UTF-8 are kept apart by their bytes. The other
+/// formats output the records of each query's alternates in turn, those of
+/// jsonl including their query.
+pub fn write_batch<W: Write>(
+    out: &mut W,
+    format: Format,
+    delimiter: u8,
+    scorer: &SimilarityScorer,
+    results: &[(PathBuf, Vec<ScoredPath>)],
+) -> io::Result<()> {
+    match format {
+        Format::Plain => {
+            for (index, (query_path, scored_paths)) in results.iter().enumerate() {
+                if index > 0 {
+                    out.write_all(&[delimiter])?;
+                }
+                out.write_all(b"==> ")?;
+                out.write_all(&path_to_bytes(query_path))?;
+                out.write_all(b" <==")?;
+                out.write_all(&[delimiter])?;
+                for (_, path) in scored_paths.iter() {
+                    out.write_all(&path_to_bytes(path))?;
+                    out.write_all(&[delimiter])?;
+                }
+            }
+            Ok(())
+        }
+        Format::Json => {
+            let documents: Vec<JsonDocument> = results
+                .iter()
+                .map(|(query_path, scored_paths)| {
+                    JsonDocument::new(query_path, scorer, scored_paths)
+                })
+                .collect();
+            serde_json::to_writer(&mut *out, &documents)?;
+            out.write_all(b"\n")
+        }
+        _ => {
+            for (query_path, scored_paths) in results.iter() {
+                write_alternates(out, format, delimiter, query_path, scorer, scored_paths)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Write a line per alternate locating it, for editors to load into their
+// lists of locations: the path, then the given text, then a message with
+// the score and kind of the alternate.
+fn write_locations<W: Write>(
+    out: &mut W,
+    delimiter: u8,
+    scored_paths: &[ScoredPath],
+    location: &str,
+) -> io::Result<()> {
+    for (score, path) in scored_paths.iter() {
+        out.write_all(&path_to_bytes(path))?;
+        write!(
+            out,
+            "{}score={:.2} kind={}",
+            location,
+            score,
+            classify(path)
+        )?;
+        out.write_all(&[delimiter])?;
+    }
+    Ok(())
+}
+
+// The kind of the alternate and the weighted score of each of its
+// components, as space separated key=value pairs.
+fn metadata(query_path: &Path, scorer: &SimilarityScorer, scored_path: &ScoredPath) -> String {
+    let scores = scorer.weigh(&scorer.components(query_path, &scored_path.1));
+    format!(
+        "kind={} leading_edge_filename={:.2} filename={:.2} path={:.2} token_filename={:.2} unordered_token_filename={:.2}",
+        classify(&scored_path.1),
+        scores.leading_edge_filename,
+        scores.filename,
+        scores.path,
+        scores.token_filename,
+        scores.unordered_token_filename
+    )
+}
+
+/// The ranked alternates for the query path as the document the json format
+/// outputs, e.g. to be sent as the result of a request.
+pub fn alternates_to_json(
+    query_path: &Path,
+    scorer: &SimilarityScorer,
+    scored_paths: &[ScoredPath],
+) -> serde_json::Value {
+    serde_json::to_value(JsonDocument::new(query_path, scorer, scored_paths))
+        .expect("alternates are always valid JSON")
+}
+
+/// The paths of the scored paths, as the plain format outputs them, joined by
+/// the delimiter.
+pub fn scored_paths_to_bytes(scored_paths: &[ScoredPath], delimiter: u8) -> Vec<u8> {
+    let matches: Vec<Vec<u8>> = scored_paths
+        .iter()
+        .map(|(_, path)| path_to_bytes(path).into_owned())
+        .collect();
+
+    matches.join(&delimiter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scored_paths_to_bytes, write_alternates, write_batch, Format};
+    use alt::path::scoring::{ScoredPath, Scorer, SimilarityScorer};
+    use serde_json;
+    use serde_json::Value;
+    use std::path::{Path, PathBuf};
+
+    fn write_to_string(format: Format, scored_paths: &[ScoredPath]) -> String {
+        let mut out: Vec<u8> = vec![];
+        write_alternates(
+            &mut out,
+            format,
+            b'\n',
+            Path::new("spec/models/user_spec.rb"),
+            &SimilarityScorer::default(),
+            scored_paths,
+        )
+        .expect("Failed to write alternates");
+        String::from_utf8(out).unwrap()
+    }
+
+    fn scored(path: &str) -> ScoredPath {
+        let score = SimilarityScorer::default()
+            .score(Path::new("spec/models/user_spec.rb"), Path::new(path));
+        (score, path.into())
+    }
+
+    #[test]
+    fn format_parses_its_names() {
+        assert_eq!("plain".parse(), Ok(Format::Plain));
+        assert_eq!("json".parse(), Ok(Format::Json));
+        assert_eq!("jsonl".parse(), Ok(Format::Jsonl));
+        assert_eq!("fzf-preview".parse(), Ok(Format::FzfPreview));
+        assert_eq!("vscode".parse(), Ok(Format::Vscode));
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn scored_paths_to_bytes_with_no_scored_paths() {
+        let scored_paths: Vec<ScoredPath> = Vec::new();
+        let val = scored_paths_to_bytes(&scored_paths, b'\n');
+
+        assert_eq!(val, b"");
+    }
+
+    #[test]
+    fn scored_paths_to_bytes_with_some_scored_paths() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (0.3, "some/path/to/a/file.ts".into()),
+            (0.2, "another/path/to/a/foo.ts".into()),
+            (0.1, "foo/bar/car/zar.ts".into()),
+        ];
+        let val = scored_paths_to_bytes(&scored_paths, b'\n');
+
+        assert_eq!(
+            val,
+            b"some/path/to/a/file.ts\nanother/path/to/a/foo.ts\nfoo/bar/car/zar.ts"
+        );
+    }
+
+    #[test]
+    fn scored_paths_to_bytes_separates_paths_with_the_delimiter() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (0.3, "some/path\nwith/a/newline.ts".into()),
+            (0.2, "another/path.ts".into()),
+        ];
+
+        assert_eq!(
+            scored_paths_to_bytes(&scored_paths, b'\0'),
+            b"some/path\nwith/a/newline.ts\0another/path.ts"
+        );
+    }
+
+    #[test]
+    fn fzf_format_outputs_score_and_path_fields() {
+        let output = write_to_string(Format::Fzf, &[(110.0133, "app/models/user.rb".into())]);
+
+        assert_eq!(output, "110.01\tapp/models/user.rb\n");
+    }
+
+    #[test]
+    fn editor_formats_locate_the_first_line_of_each_alternate() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (49.334, "app/models/user.rb".into()),
+            (0.6, "spec/factories/users.rb".into()),
+        ];
+
+        assert_eq!(
+            write_to_string(Format::Quickfix, &scored_paths),
+            "app/models/user.rb:1:1: score=49.33 kind=implementation\n\
+             spec/factories/users.rb:1:1: score=0.60 kind=fixture\n"
+        );
+        assert_eq!(
+            write_to_string(Format::Emacs, &scored_paths[..1]),
+            "app/models/user.rb:1:1: info: score=49.33 kind=implementation\n"
+        );
+        assert_eq!(
+            write_to_string(Format::Vscode, &scored_paths[..1]),
+            "app/models/user.rb(1,1): info ALT1: score=49.33 kind=implementation\n"
+        );
+    }
+
+    #[test]
+    fn fzf_preview_format_adds_the_kind_and_score_components() {
+        let output = write_to_string(Format::FzfPreview, &[scored("app/models/user.rb")]);
+        let fields: Vec<&str> = output.trim_end().split('\t').collect();
+
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[1], "app/models/user.rb");
+        assert!(fields[2].starts_with("kind=implementation leading_edge_filename="));
+        assert!(fields[2].ends_with("unordered_token_filename=0.00"));
+    }
+
+    #[test]
+    fn json_format_outputs_query_weights_and_scored_alternates() {
+        let output = write_to_string(
+            Format::Json,
+            &[scored("app/models/user.rb"), scored("lib/user.rb")],
+        );
+        let document: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(document["query"], "spec/models/user_spec.rb");
+        assert_eq!(document["weights"]["leading_edge_filename"], 100.0);
+        assert_eq!(document["weights"]["filename"], 10.0);
+        assert_eq!(document["weights"]["path"], 1.0);
+
+        let alternates = document["alternates"].as_array().unwrap();
+        assert_eq!(alternates.len(), 2);
+        assert_eq!(alternates[0]["path"], "app/models/user.rb");
+        assert_eq!(alternates[1]["path"], "lib/user.rb");
+        assert!(alternates[0].get("path_bytes").is_none());
+
+        let components = &alternates[0]["components"];
+        let leading_edge = &components["leading_edge_filename"];
+        assert!(leading_edge["similarity"].as_f64().unwrap() > 0.0);
+        assert!(
+            (leading_edge["score"].as_f64().unwrap()
+                - 100.0 * leading_edge["similarity"].as_f64().unwrap())
+            .abs()
+                < 1e-3
+        );
+        let total: f64 = [
+            "leading_edge_filename",
+            "filename",
+            "path",
+            "token_filename",
+            "unordered_token_filename",
+        ]
+        .iter()
+        .map(|name| components[*name]["score"].as_f64().unwrap())
+        .sum();
+        assert!((alternates[0]["score"].as_f64().unwrap() - total).abs() < 1e-3);
+    }
+
+    #[test]
+    fn json_format_with_no_alternates_outputs_an_empty_list() {
+        let output = write_to_string(Format::Json, &[]);
+        let document: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(document["alternates"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn jsonl_format_outputs_a_line_per_alternate_with_the_query_and_weights() {
+        let output = write_to_string(
+            Format::Jsonl,
+            &[scored("app/models/user.rb"), scored("lib/user.rb")],
+        );
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["query"], "spec/models/user_spec.rb");
+        assert_eq!(lines[1]["path"], "lib/user.rb");
+        assert_eq!(lines[1]["weights"]["path"], 1.0);
+        assert!(lines[1]["components"]["path"]["similarity"].is_number());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn json_format_includes_the_bytes_of_paths_that_are_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"app/\xff.rb"));
+        let output = write_to_string(Format::Jsonl, &[(1.0, path.to_path_buf())]);
+        let line: Value = serde_json::from_str(output.trim_end()).unwrap();
+
+        assert_eq!(line["path"], "app/\u{fffd}.rb");
+        assert_eq!(
+            line["path_bytes"],
+            serde_json::json!([97, 112, 112, 47, 255, 46, 114, 98])
+        );
+    }
+
+    fn write_batch_to_string(format: Format, results: &[(PathBuf, Vec<ScoredPath>)]) -> String {
+        let mut out: Vec<u8> = vec![];
+        write_batch(
+            &mut out,
+            format,
+            b'\n',
+            &SimilarityScorer::default(),
+            results,
+        )
+        .expect("Failed to write alternates");
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain_batch_groups_the_alternates_under_a_header_per_query() {
+        let output = write_batch_to_string(
+            Format::Plain,
+            &[
+                (
+                    "spec/models/user_spec.rb".into(),
+                    vec![scored("app/models/user.rb"), scored("lib/user.rb")],
+                ),
+                ("lib/user.rb".into(), vec![]),
+            ],
+        );
+
+        assert_eq!(
+            output,
+            "==> spec/models/user_spec.rb <==\napp/models/user.rb\nlib/user.rb\n\n==> lib/user.rb <==\n"
+        );
+    }
+
+    #[test]
+    fn json_batch_outputs_the_documents_in_query_order() {
+        let output = write_batch_to_string(
+            Format::Json,
+            &[
+                (
+                    "spec/models/user_spec.rb".into(),
+                    vec![scored("app/models/user.rb")],
+                ),
+                ("lib/user.rb".into(), vec![]),
+            ],
+        );
+        let document: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(document[0]["query"], "spec/models/user_spec.rb");
+        assert_eq!(document[0]["alternates"][0]["path"], "app/models/user.rb");
+        assert_eq!(document[1]["query"], "lib/user.rb");
+        assert_eq!(document[1]["alternates"], serde_json::json!([]));
+        assert_eq!(document.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn json_batch_keeps_queries_apart_that_are_only_told_apart_by_their_bytes() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let output = write_batch_to_string(
+            Format::Json,
+            &[
+                (
+                    OsStr::from_bytes(b"caf\xe9.rb").into(),
+                    vec![scored("cafe_spec.rb")],
+                ),
+                (OsStr::from_bytes(b"caf\xe8.rb").into(), vec![]),
+            ],
+        );
+        let document: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(document[0]["query"], document[1]["query"]);
+        assert_eq!(
+            document[0]["query_bytes"],
+            serde_json::json!([99, 97, 102, 233, 46, 114, 98])
+        );
+        assert_eq!(document[0]["alternates"][0]["path"], "cafe_spec.rb");
+        assert_eq!(
+            document[1]["query_bytes"],
+            serde_json::json!([99, 97, 102, 232, 46, 114, 98])
+        );
+        assert_eq!(document[1]["alternates"], serde_json::json!([]));
+    }
+}
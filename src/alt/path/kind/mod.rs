@@ -0,0 +1,196 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+
+/// The role a file plays in a project, as inferred from its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Implementation,
+    Test,
+    Fixture,
+    Mock,
+    Story,
+}
+
+impl FileKind {
+    /// The kind of file that is the natural alternate for a file of this
+    /// kind, e.g. the implementation for a test and a test for an
+    /// implementation.
+    pub fn opposite(self) -> FileKind {
+        match self {
+            FileKind::Implementation => FileKind::Test,
+            FileKind::Test => FileKind::Implementation,
+            FileKind::Fixture => FileKind::Test,
+            FileKind::Mock => FileKind::Implementation,
+            FileKind::Story => FileKind::Implementation,
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FileKind::Implementation => "implementation",
+            FileKind::Test => "test",
+            FileKind::Fixture => "fixture",
+            FileKind::Mock => "mock",
+            FileKind::Story => "story",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const TEST_DIRS: &[&str] = &["test", "tests", "spec", "specs", "__tests__", "features"];
+const FIXTURE_DIRS: &[&str] = &[
+    "fixtures",
+    "__fixtures__",
+    "testdata",
+    "factories",
+    "__snapshots__",
+];
+const MOCK_DIRS: &[&str] = &["mocks", "__mocks__"];
+
+/// Classify the given path based on the naming conventions of its filename
+/// and the directories it lives in.
+pub fn classify<P: AsRef<Path>>(path: P) -> FileKind {
+    let path = path.as_ref();
+    let filename = path
+        .file_name()
+        .map(|f| f.to_string_lossy())
+        .unwrap_or_default();
+    let dirs: Vec<Cow<str>> = path
+        .parent()
+        .map(|parent| parent.iter().map(|dir| dir.to_string_lossy()).collect())
+        .unwrap_or_default();
+
+    // split foo.test.ts into the base foo, the markers [test] and extension ts
+    let parts: Vec<&str> = filename.split('.').collect();
+    let base = parts[0];
+    let (markers, extension) = if parts.len() > 1 {
+        (&parts[1..parts.len() - 1], parts[parts.len() - 1])
+    } else {
+        (&parts[1..], "")
+    };
+
+    let in_any_dir = |names: &[&str]| dirs.iter().any(|dir| names.contains(&dir.as_ref()));
+    let has_marker = |names: &[&str]| markers.iter().any(|marker| names.contains(marker));
+
+    if has_marker(&["stories", "story"]) {
+        FileKind::Story
+    } else if in_any_dir(FIXTURE_DIRS)
+        || has_marker(&["fixture", "fixtures"])
+        || base.ends_with("_fixture")
+        || extension == "snap"
+    {
+        FileKind::Fixture
+    } else if in_any_dir(MOCK_DIRS)
+        || has_marker(&["mock", "mocks"])
+        || base.ends_with("_mock")
+        || base.ends_with("_mocks")
+    {
+        FileKind::Mock
+    } else if in_any_dir(TEST_DIRS)
+        || has_marker(&["test", "tests", "spec"])
+        || base.ends_with("_test")
+        || base.ends_with("_tests")
+        || base.ends_with("_spec")
+        || (extension == "py" && base.starts_with("test_"))
+        || has_camel_case_test_suffix(base)
+        || extension == "feature"
+    {
+        FileKind::Test
+    } else {
+        FileKind::Implementation
+    }
+}
+
+// Matches FooTest, FooTests and FooSpec but not Latest or Inspect.
+fn has_camel_case_test_suffix(base: &str) -> bool {
+    ["Test", "Tests", "Spec", "Specs"].iter().any(|suffix| {
+        base.len() > suffix.len()
+            && base.ends_with(suffix)
+            && base[..base.len() - suffix.len()]
+                .chars()
+                .last()
+                .is_some_and(|c| c.is_lowercase() || c.is_ascii_digit())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, FileKind};
+
+    #[test]
+    fn classify_recognizes_test_filename_conventions() {
+        assert_eq!(classify("spec/models/user_spec.rb"), FileKind::Test);
+        assert_eq!(classify("pkg/foo_test.go"), FileKind::Test);
+        assert_eq!(classify("src/foo.test.ts"), FileKind::Test);
+        assert_eq!(classify("src/foo.spec.jsx"), FileKind::Test);
+        assert_eq!(classify("pkg/test_foo.py"), FileKind::Test);
+        assert_eq!(classify("src/com/acme/FooTest.java"), FileKind::Test);
+        assert_eq!(classify("Sources/FooSpec.swift"), FileKind::Test);
+        assert_eq!(classify("features/login.feature"), FileKind::Test);
+    }
+
+    #[test]
+    fn classify_recognizes_test_directories() {
+        assert_eq!(classify("src/__tests__/foo.ts"), FileKind::Test);
+        assert_eq!(classify("tests/parser.rs"), FileKind::Test);
+        assert_eq!(classify("src/test/java/Foo.java"), FileKind::Test);
+    }
+
+    #[test]
+    fn classify_recognizes_implementations() {
+        assert_eq!(
+            classify("app/queues/fee/user_fee_submission.rb"),
+            FileKind::Implementation
+        );
+        assert_eq!(
+            classify("src/models/nft-wallet.ts"),
+            FileKind::Implementation
+        );
+        assert_eq!(classify("lib/latest.rb"), FileKind::Implementation);
+        assert_eq!(classify("src/Latest.java"), FileKind::Implementation);
+        assert_eq!(
+            classify("app/models/test_result.rb"),
+            FileKind::Implementation
+        );
+        assert_eq!(classify("Makefile"), FileKind::Implementation);
+    }
+
+    #[test]
+    fn classify_recognizes_fixtures() {
+        assert_eq!(classify("spec/fixtures/users.yml"), FileKind::Fixture);
+        assert_eq!(classify("pkg/testdata/input.txt"), FileKind::Fixture);
+        assert_eq!(classify("spec/factories/users.rb"), FileKind::Fixture);
+        assert_eq!(
+            classify("src/__snapshots__/foo.test.ts.snap"),
+            FileKind::Fixture
+        );
+    }
+
+    #[test]
+    fn classify_recognizes_mocks() {
+        assert_eq!(
+            classify("src/models/mocks/nft-wallet.mocks.ts"),
+            FileKind::Mock
+        );
+        assert_eq!(classify("src/__mocks__/fs.js"), FileKind::Mock);
+        assert_eq!(classify("pkg/client_mock.go"), FileKind::Mock);
+    }
+
+    #[test]
+    fn classify_recognizes_stories() {
+        assert_eq!(
+            classify("src/components/Button.stories.tsx"),
+            FileKind::Story
+        );
+    }
+
+    #[test]
+    fn opposite_of_test_and_implementation_are_each_other() {
+        assert_eq!(FileKind::Test.opposite(), FileKind::Implementation);
+        assert_eq!(FileKind::Implementation.opposite(), FileKind::Test);
+        assert_eq!(FileKind::Story.opposite(), FileKind::Implementation);
+    }
+}
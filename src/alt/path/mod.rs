@@ -1,2 +1,3 @@
+pub mod kind;
 pub mod scoring;
 pub mod utils;
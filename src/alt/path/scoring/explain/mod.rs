@@ -0,0 +1,322 @@
+use super::{
+    find_longest_common_substring, find_longest_leading_edge_common_substring_length, graphemes,
+    path_text, tokenize, ScoreComponents, SimilarityScorer, TextUnit,
+};
+use std::fmt;
+use std::path::Path;
+
+/// The longest common substring of two compared strings, with where it
+/// starts in each, in the units they were compared in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonSubstring {
+    pub text: String,
+    pub length: usize,
+    pub query_start: usize,
+    pub candidate_start: usize,
+}
+
+/// A comparison of the same part, e.g. the file stem, of the query path and
+/// a candidate path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub query: String,
+    pub candidate: String,
+    pub common: CommonSubstring,
+}
+
+/// Everything `SimilarityScorer` computed when scoring a candidate path for
+/// a query path, so that it is possible to see why a candidate ranked where
+/// it did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub query_path: String,
+    pub candidate_path: String,
+    pub scorer: SimilarityScorer,
+    /// The file stems, if both paths have one.
+    pub filename: Option<Comparison>,
+    pub leading_edge_filename_length: usize,
+    /// The parent paths, if both paths have one.
+    pub path: Option<Comparison>,
+    pub query_tokens: Vec<String>,
+    pub candidate_tokens: Vec<String>,
+    pub similarities: ScoreComponents,
+    pub scores: ScoreComponents,
+}
+
+impl Explanation {
+    pub fn total(&self) -> f32 {
+        self.scores.total()
+    }
+}
+
+fn units(text_unit: TextUnit, s: &str) -> Vec<&str> {
+    match text_unit {
+        TextUnit::Char => s
+            .char_indices()
+            .map(|(i, c)| &s[i..i + c.len_utf8()])
+            .collect(),
+        TextUnit::Grapheme => graphemes(s),
+    }
+}
+
+fn compare(text_unit: TextUnit, query: &str, candidate: &str) -> Comparison {
+    let query_units = units(text_unit, query);
+    let candidate_units = units(text_unit, candidate);
+    let (length, query_start, candidate_start) =
+        find_longest_common_substring(&query_units, &candidate_units);
+    let length = length as usize;
+
+    Comparison {
+        query: query.to_string(),
+        candidate: candidate.to_string(),
+        common: CommonSubstring {
+            text: query_units[query_start..query_start + length].concat(),
+            length,
+            query_start,
+            candidate_start,
+        },
+    }
+}
+
+/// Explain how the scorer scores the candidate path for the query path.
+pub fn explain(scorer: &SimilarityScorer, query_path: &Path, candidate_path: &Path) -> Explanation {
+    let similarities = scorer.components(query_path, candidate_path);
+    let query_text = path_text(query_path);
+    let candidate_text = path_text(candidate_path);
+    let query_path = Path::new(query_text.as_ref());
+    let candidate_path = Path::new(candidate_text.as_ref());
+
+    let stems = match (
+        query_path.file_stem().and_then(|f| f.to_str()),
+        candidate_path.file_stem().and_then(|f| f.to_str()),
+    ) {
+        (Some(query_stem), Some(candidate_stem)) => Some((query_stem, candidate_stem)),
+        _ => None,
+    };
+    let parents = match (
+        query_path.parent().and_then(|f| f.to_str()),
+        candidate_path.parent().and_then(|f| f.to_str()),
+    ) {
+        (Some(query_parent), Some(candidate_parent)) => Some((query_parent, candidate_parent)),
+        _ => None,
+    };
+
+    Explanation {
+        query_path: query_text.to_string(),
+        candidate_path: candidate_text.to_string(),
+        scorer: *scorer,
+        filename: stems.map(|(query, candidate)| compare(scorer.text_unit, query, candidate)),
+        leading_edge_filename_length: stems.map_or(0, |(query, candidate)| {
+            find_longest_leading_edge_common_substring_length(
+                &units(scorer.text_unit, query),
+                &units(scorer.text_unit, candidate),
+            ) as usize
+        }),
+        path: parents.map(|(query, candidate)| compare(scorer.text_unit, query, candidate)),
+        query_tokens: stems.map_or(vec![], |(query, _)| tokenize(query)),
+        candidate_tokens: stems.map_or(vec![], |(_, candidate)| tokenize(candidate)),
+        similarities,
+        scores: scorer.weigh(&similarities),
+    }
+}
+
+fn write_comparison(
+    f: &mut fmt::Formatter,
+    name: &str,
+    comparison: &Option<Comparison>,
+) -> fmt::Result {
+    match comparison {
+        Some(comparison) => {
+            writeln!(
+                f,
+                "{}: {:?} vs {:?}",
+                name, comparison.query, comparison.candidate
+            )?;
+            if comparison.common.length == 0 {
+                writeln!(f, "  longest common substring: none")
+            } else {
+                writeln!(
+                    f,
+                    "  longest common substring: {:?} (length {}) at {} in query, {} in candidate",
+                    comparison.common.text,
+                    comparison.common.length,
+                    comparison.common.query_start,
+                    comparison.common.candidate_start
+                )
+            }
+        }
+        None => writeln!(f, "{}: not both present", name),
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text_unit = match self.scorer.text_unit {
+            TextUnit::Char => "characters",
+            TextUnit::Grapheme => "graphemes",
+        };
+        writeln!(f, "query:     {}", self.query_path)?;
+        writeln!(f, "candidate: {}", self.candidate_path)?;
+        writeln!(f, "compared in {}", text_unit)?;
+        writeln!(f)?;
+
+        write_comparison(f, "file stems", &self.filename)?;
+        if self.filename.is_some() {
+            writeln!(
+                f,
+                "  leading edge match length: {}",
+                self.leading_edge_filename_length
+            )?;
+            writeln!(
+                f,
+                "  tokens: {:?} vs {:?}",
+                self.query_tokens, self.candidate_tokens
+            )?;
+        }
+        write_comparison(f, "parent paths", &self.path)?;
+        writeln!(f)?;
+
+        let rows = [
+            (
+                "leading_edge_filename",
+                self.similarities.leading_edge_filename,
+                self.scorer.leading_edge_filename_weight,
+                self.scores.leading_edge_filename,
+            ),
+            (
+                "filename",
+                self.similarities.filename,
+                self.scorer.filename_weight,
+                self.scores.filename,
+            ),
+            (
+                "path",
+                self.similarities.path,
+                self.scorer.path_weight,
+                self.scores.path,
+            ),
+            (
+                "token_filename",
+                self.similarities.token_filename,
+                self.scorer.token_filename_weight,
+                self.scores.token_filename,
+            ),
+            (
+                "unordered_token_filename",
+                self.similarities.unordered_token_filename,
+                self.scorer.unordered_token_filename_weight,
+                self.scores.unordered_token_filename,
+            ),
+        ];
+        writeln!(
+            f,
+            "{:<26}{:>12}{:>10}{:>14}",
+            "component", "similarity", "weight", "contribution"
+        )?;
+        for (name, similarity, weight, score) in rows.iter() {
+            writeln!(
+                f,
+                "{:<26}{:>12.6}{:>10}{:>14.6}",
+                name, similarity, weight, score
+            )?;
+        }
+        write!(f, "{:<26}{:>36.6}", "total", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, CommonSubstring};
+    use alt::path::scoring::{Scorer, SimilarityScorer, TextUnit};
+    use std::path::Path;
+
+    #[test]
+    fn explain_reports_the_compared_stems_and_parents() {
+        let explanation = explain(
+            &SimilarityScorer::default(),
+            Path::new("spec/models/user_spec.rb"),
+            Path::new("app/models/user.rb"),
+        );
+
+        let filename = explanation.filename.unwrap();
+        assert_eq!(filename.query, "user_spec");
+        assert_eq!(filename.candidate, "user");
+        assert_eq!(
+            filename.common,
+            CommonSubstring {
+                text: "user".to_string(),
+                length: 4,
+                query_start: 0,
+                candidate_start: 0,
+            }
+        );
+        assert_eq!(explanation.leading_edge_filename_length, 4);
+
+        let path = explanation.path.unwrap();
+        assert_eq!(path.query, "spec/models");
+        assert_eq!(path.candidate, "app/models");
+        assert_eq!(path.common.text, "/models");
+        assert_eq!(path.common.query_start, 4);
+        assert_eq!(path.common.candidate_start, 3);
+
+        assert_eq!(explanation.query_tokens, vec!["user", "spec"]);
+        assert_eq!(explanation.candidate_tokens, vec!["user"]);
+    }
+
+    #[test]
+    fn explain_total_matches_the_score() {
+        let scorer = SimilarityScorer::default().with_token_filename_weights(2.0, 3.0);
+        let query_path = Path::new("src/components/UserCard.tsx");
+        let candidate_path = Path::new("src/components/__tests__/user-card.test.tsx");
+
+        let explanation = explain(&scorer, query_path, candidate_path);
+        assert_eq!(
+            explanation.total(),
+            scorer.score(query_path, candidate_path)
+        );
+        assert_eq!(
+            explanation.scores.filename,
+            scorer.filename_weight * explanation.similarities.filename
+        );
+    }
+
+    #[test]
+    fn explain_positions_are_in_the_units_compared() {
+        let explanation = explain(
+            &SimilarityScorer::default(),
+            Path::new("資料/ユーザー.rb"),
+            Path::new("資料/新ユーザー.rb"),
+        );
+
+        let filename = explanation.filename.unwrap();
+        assert_eq!(filename.common.text, "ユーザー");
+        assert_eq!(filename.common.length, 4);
+        assert_eq!(filename.common.candidate_start, 1);
+
+        let explanation = explain(
+            &SimilarityScorer::default().with_text_unit(TextUnit::Grapheme),
+            Path::new("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}a.rb"),
+            Path::new("x\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}a.rb"),
+        );
+        assert_eq!(explanation.filename.unwrap().common.length, 2);
+    }
+
+    #[test]
+    fn explanation_display_includes_the_components() {
+        let explanation = explain(
+            &SimilarityScorer::default(),
+            Path::new("spec/models/user_spec.rb"),
+            Path::new("app/models/user.rb"),
+        );
+        let text = explanation.to_string();
+
+        assert!(text.contains("file stems: \"user_spec\" vs \"user\""));
+        assert!(text.contains(
+            "longest common substring: \"user\" (length 4) at 0 in query, 0 in candidate"
+        ));
+        assert!(text.contains("leading edge match length: 4"));
+        assert!(text.contains("parent paths: \"spec/models\" vs \"app/models\""));
+        assert!(text.contains("leading_edge_filename"));
+        assert!(text.contains("total"));
+    }
+}
@@ -0,0 +1,312 @@
+//! Longest common substring of two sequences, e.g. the characters of two
+//! paths, along with where it starts in each.
+//!
+//! Short sequences are compared with dynamic programming keeping a single
+//! row of the table, O(N*M) time and O(min(N, M)) memory, in a buffer reused
+//! by each thread. Long ones, e.g. deeply nested parent paths, are compared
+//! with a suffix automaton, O(N + M) time.
+
+use std::cell::RefCell;
+
+/// The length of a longest common substring of two sequences, followed by
+/// where it starts in each of them.
+pub type CommonSubstring = (usize, usize, usize);
+
+/// The length both sequences have to be at least for the suffix automaton
+/// to be used rather than the dynamic programming table.
+pub const SUFFIX_AUTOMATON_MIN_LEN: usize = 160;
+
+/// Find the longest common substring of s1 and s2 along with where it starts
+/// in each. When there are several of the same length the one ending first
+/// in s1, and then first in s2, is returned.
+pub fn longest_common_substring<T: PartialEq>(s1: &[T], s2: &[T]) -> CommonSubstring {
+    if s1.len().min(s2.len()) >= SUFFIX_AUTOMATON_MIN_LEN {
+        suffix_automaton(s1, s2)
+    } else {
+        rolling_row(s1, s2)
+    }
+}
+
+thread_local! {
+    static ROW: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `longest_common_substring` by dynamic programming, keeping only the row
+/// of the table for the shorter of the sequences.
+pub fn rolling_row<T: PartialEq>(s1: &[T], s2: &[T]) -> CommonSubstring {
+    if s1.is_empty() || s2.is_empty() {
+        return (0, 0, 0);
+    }
+
+    let swapped = s2.len() > s1.len();
+    let (outer, inner) = if swapped { (s2, s1) } else { (s1, s2) };
+
+    ROW.with(|row| {
+        let mut row = row.borrow_mut();
+        row.clear();
+        row.resize(inner.len(), 0);
+
+        let mut longest_length = 0;
+        let mut longest_end = (0, 0);
+
+        for (i, a) in outer.iter().enumerate() {
+            // walk the row backwards so row[j - 1] is still the previous
+            // row's value when row[j] is computed
+            for j in (0..inner.len()).rev() {
+                if *a != inner[j] {
+                    row[j] = 0;
+                    continue;
+                }
+                let length = if j > 0 { row[j - 1] } else { 0 } + 1;
+                row[j] = length;
+                if length >= longest_length {
+                    let end = if swapped { (j, i) } else { (i, j) };
+                    if length > longest_length || end < longest_end {
+                        longest_length = length;
+                        longest_end = end;
+                    }
+                }
+            }
+        }
+
+        starts(longest_length as usize, longest_end)
+    })
+}
+
+struct State<'a, T: 'a> {
+    len: usize,
+    link: usize,
+    // where the first occurrence in s1 of the substrings of this state ends
+    first_end: usize,
+    transitions: Vec<(&'a T, usize)>,
+}
+
+fn transition<T: PartialEq>(state: &State<T>, item: &T) -> Option<usize> {
+    state
+        .transitions
+        .iter()
+        .find(|(t, _)| *t == item)
+        .map(|(_, next)| *next)
+}
+
+/// `longest_common_substring` by building a suffix automaton of s1 and
+/// running s2 through it.
+pub fn suffix_automaton<T: PartialEq>(s1: &[T], s2: &[T]) -> CommonSubstring {
+    if s1.is_empty() || s2.is_empty() {
+        return (0, 0, 0);
+    }
+
+    // state 0 is the initial state, whose link is never followed
+    let mut states: Vec<State<T>> = Vec::with_capacity(2 * s1.len());
+    states.push(State {
+        len: 0,
+        link: 0,
+        first_end: 0,
+        transitions: vec![],
+    });
+    let mut last = 0;
+
+    for (i, item) in s1.iter().enumerate() {
+        let current = states.len();
+        states.push(State {
+            len: states[last].len + 1,
+            link: 0,
+            first_end: i,
+            transitions: vec![],
+        });
+
+        let mut p = Some(last);
+        while let Some(state) = p {
+            if transition(&states[state], item).is_some() {
+                break;
+            }
+            states[state].transitions.push((item, current));
+            p = if state == 0 {
+                None
+            } else {
+                Some(states[state].link)
+            };
+        }
+
+        if let Some(p) = p {
+            let q = transition(&states[p], item).unwrap();
+            if states[p].len + 1 == states[q].len {
+                states[current].link = q;
+            } else {
+                let clone = states.len();
+                let cloned = State {
+                    len: states[p].len + 1,
+                    link: states[q].link,
+                    first_end: states[q].first_end,
+                    transitions: states[q].transitions.clone(),
+                };
+                states.push(cloned);
+
+                let mut p = Some(p);
+                while let Some(state) = p {
+                    match states[state]
+                        .transitions
+                        .iter_mut()
+                        .find(|(t, _)| *t == item)
+                    {
+                        Some(transition) if transition.1 == q => transition.1 = clone,
+                        _ => break,
+                    }
+                    p = if state == 0 {
+                        None
+                    } else {
+                        Some(states[state].link)
+                    };
+                }
+                states[q].link = clone;
+                states[current].link = clone;
+            }
+        }
+
+        last = current;
+    }
+
+    let mut state = 0;
+    let mut length = 0;
+    let mut longest_length = 0;
+    let mut longest_end = (0, 0);
+
+    for (j, item) in s2.iter().enumerate() {
+        // fall back to shorter suffixes of the match until it can be extended
+        while state != 0 && transition(&states[state], item).is_none() {
+            state = states[state].link;
+            length = states[state].len;
+        }
+        match transition(&states[state], item) {
+            Some(next) => {
+                state = next;
+                length += 1;
+            }
+            None => continue,
+        }
+
+        if length >= longest_length {
+            let end = (states[state].first_end, j);
+            if length > longest_length || end < longest_end {
+                longest_length = length;
+                longest_end = end;
+            }
+        }
+    }
+
+    starts(longest_length, longest_end)
+}
+
+fn starts(length: usize, end: (usize, usize)) -> CommonSubstring {
+    if length == 0 {
+        return (0, 0, 0);
+    }
+
+    (length, end.0 + 1 - length, end.1 + 1 - length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{longest_common_substring, rolling_row, suffix_automaton, CommonSubstring};
+
+    // The full N*M table, as alt originally computed it, to compare against.
+    fn full_table<T: PartialEq>(s1: &[T], s2: &[T]) -> CommonSubstring {
+        let mut m = vec![vec![0; s2.len()]; s1.len()];
+        let mut longest_length = 0;
+        let mut longest_end = (0, 0);
+
+        for i in 0..s1.len() {
+            for j in 0..s2.len() {
+                if s1[i] == s2[j] {
+                    m[i][j] = 1;
+                    if i > 0 && j > 0 {
+                        m[i][j] += m[i - 1][j - 1];
+                    }
+                    if m[i][j] > longest_length {
+                        longest_length = m[i][j];
+                        longest_end = (i, j);
+                    }
+                }
+            }
+        }
+
+        if longest_length == 0 {
+            return (0, 0, 0);
+        }
+        (
+            longest_length,
+            longest_end.0 + 1 - longest_length,
+            longest_end.1 + 1 - longest_length,
+        )
+    }
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    // A deterministic sequence over a small alphabet so that there are many
+    // repeats & ties.
+    fn sequence(seed: &mut u64, len: usize, alphabet: u8) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                *seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b'a' + ((*seed >> 33) % alphabet as u64) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn implementations_find_the_same_common_substring_as_the_full_table() {
+        let mut seed = 42;
+        for len1 in 0..24 {
+            for len2 in [0, 1, 3, 7, 16, 23] {
+                for alphabet in [1, 2, 4, 26] {
+                    let s1 = sequence(&mut seed, len1, alphabet);
+                    let s2 = sequence(&mut seed, len2, alphabet);
+                    let expected = full_table(&s1, &s2);
+
+                    assert_eq!(rolling_row(&s1, &s2), expected, "{:?} {:?}", s1, s2);
+                    assert_eq!(suffix_automaton(&s1, &s2), expected, "{:?} {:?}", s1, s2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn implementations_agree_on_long_paths() {
+        let mut seed = 7;
+        for _ in 0..20 {
+            let s1 = sequence(&mut seed, 300, 3);
+            let s2 = sequence(&mut seed, 200, 3);
+            let expected = full_table(&s1, &s2);
+
+            assert_eq!(rolling_row(&s1, &s2), expected);
+            assert_eq!(suffix_automaton(&s1, &s2), expected);
+            assert_eq!(longest_common_substring(&s1, &s2), expected);
+        }
+    }
+
+    #[test]
+    fn implementations_return_the_first_of_equally_long_substrings() {
+        let s1 = chars("xab_cd_ab");
+        let s2 = chars("cd_ab");
+
+        assert_eq!(full_table(&s1, &s2), (5, 4, 0));
+        assert_eq!(rolling_row(&s1, &s2), (5, 4, 0));
+        assert_eq!(suffix_automaton(&s1, &s2), (5, 4, 0));
+
+        let s1 = chars("ab_cd");
+        let s2 = chars("cd_xx_ab");
+        assert_eq!(rolling_row(&s1, &s2), full_table(&s1, &s2));
+        assert_eq!(suffix_automaton(&s1, &s2), full_table(&s1, &s2));
+    }
+
+    #[test]
+    fn implementations_with_no_common_substring() {
+        assert_eq!(rolling_row(&chars("abc"), &chars("xyz")), (0, 0, 0));
+        assert_eq!(suffix_automaton(&chars("abc"), &chars("xyz")), (0, 0, 0));
+        assert_eq!(suffix_automaton(&chars(""), &chars("xyz")), (0, 0, 0));
+    }
+}
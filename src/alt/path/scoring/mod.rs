@@ -1,70 +1,342 @@
 use super::utils::cleanse_path;
-use std::path::Path;
-
-pub type ScoredPath = (f32, String);
-
-fn find_longest_common_substring_length(s1: &str, s2: &str) -> i32 {
-    // Currently this is implemented using a dynamic programming solution similar
-    // to http://www.geeksforgeeks.org/longest-common-substring/. This is O(N*M)
-    // where N is the length of one string and M is the length of the other
-    // string.
-    //
-    // Another option would of course be to explore using something like a
-    // suffix tree to solve this problem, something like, the following.
-    // http://www.geeksforgeeks.org/suffix-tree-application-5-longest-common-substring-2/
-    // This is O(M+N) to build a Generalized Suffix Tree and O(M+N) to find the
-    // the longest common substring via depth first search.
-    //
-    // Beyond that we would have to explore not caring about longest substring
-    // and moving to a similarity ranking algorithm that maybe cares about
-    // subsequences rather that substrings, etc.
-    if s1.is_empty() || s2.is_empty() {
-        return 0;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub mod explain;
+pub mod lcs;
+
+pub type ScoredPath = (f32, PathBuf);
+
+/// A strategy for scoring how good of an alternate a candidate path is for a
+/// query path. Higher scores indicate better alternates.
+pub trait Scorer {
+    fn score(&self, query_path: &Path, candidate_path: &Path) -> f32;
+
+    /// Score a candidate path whose parts were computed ahead of time, e.g.
+    /// read from the index, given the parts of the query path too. Scorers
+    /// that do not compare the parts of paths score the paths themselves.
+    fn score_parts(
+        &self,
+        query_path: &Path,
+        _query_parts: &PathParts,
+        candidate_path: &Path,
+        _candidate_parts: &PathParts,
+    ) -> f32 {
+        self.score(query_path, candidate_path)
     }
+}
 
-    let mut m: Vec<Vec<i32>> = Vec::with_capacity(s1.len());
-    for _ in 0..s1.len() {
-        let v: Vec<i32> = vec![0; s2.len()];
-        m.push(v);
+/// The parts of a path `SimilarityScorer` compares: the NFC normalized file
+/// stem and parent path, and the tokens of the file stem. They only depend on
+/// the path, so they can be computed once, e.g. when the path is indexed.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct PathParts {
+    pub stem: Option<String>,
+    pub parent: Option<String>,
+    pub tokens: Vec<String>,
+}
+
+impl PathParts {
+    pub fn new(path: &Path) -> Self {
+        let mut parts = PathParts::without_tokens(path);
+        parts.tokens = parts.stem.as_deref().map_or(vec![], tokenize);
+        parts
     }
 
-    let mut longest_length = 0;
+    // The tokens are only needed when the token weights are not zero.
+    fn without_tokens(path: &Path) -> Self {
+        let text = path_text(path);
+        let path = Path::new(text.as_ref());
+        PathParts {
+            stem: path.file_stem().and_then(|f| f.to_str()).map(String::from),
+            parent: path.parent().and_then(|f| f.to_str()).map(String::from),
+            tokens: vec![],
+        }
+    }
+}
 
-    let s1_bytes = s1.as_bytes();
-    let s2_bytes = s2.as_bytes();
+/// A possible alternate path, possibly along with its parts computed ahead
+/// of time.
+pub trait Candidate {
+    fn path(&self) -> &Path;
 
-    for i in 0..s1.len() {
-        for j in 0..s2.len() {
-            if s1_bytes[i] == s2_bytes[j] {
-                m[i][j] = 1;
-                if i > 0 && j > 0 {
-                    m[i][j] += m[i - 1][j - 1];
-                }
-                if m[i][j] > longest_length {
-                    longest_length = m[i][j];
-                }
-            }
+    fn parts(&self) -> Option<&PathParts> {
+        None
+    }
+}
+
+impl Candidate for PathBuf {
+    fn path(&self) -> &Path {
+        self
+    }
+}
+
+impl Candidate for &PathBuf {
+    fn path(&self) -> &Path {
+        self
+    }
+}
+
+impl Candidate for &Path {
+    fn path(&self) -> &Path {
+        self
+    }
+}
+
+/// The unit text is compared in when scoring. Paths are always normalized to
+/// NFC first, so precomposed and decomposed accents compare equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextUnit {
+    /// Unicode scalar values.
+    #[default]
+    Char,
+    /// Extended grapheme clusters, so e.g. an emoji sequence joined by
+    /// zero-width joiners only matches as a whole.
+    Grapheme,
+}
+
+impl TextUnit {
+    fn similarity_ratio(self, s1: &str, s2: &str) -> f32 {
+        match self {
+            TextUnit::Char => similarity_ratio(&chars(s1), &chars(s2)),
+            TextUnit::Grapheme => similarity_ratio(&graphemes(s1), &graphemes(s2)),
         }
     }
 
-    longest_length
+    fn leading_edge_similarity_ratio(self, s1: &str, s2: &str) -> f32 {
+        match self {
+            TextUnit::Char => leading_edge_similarity_ratio(&chars(s1), &chars(s2)),
+            TextUnit::Grapheme => leading_edge_similarity_ratio(&graphemes(s1), &graphemes(s2)),
+        }
+    }
+}
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+fn graphemes(s: &str) -> Vec<&str> {
+    s.graphemes(true).collect()
+}
+
+fn normalize(s: &str) -> Cow<'_, str> {
+    match is_nfc_quick(s.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(s),
+        _ => Cow::Owned(s.nfc().collect()),
+    }
 }
 
-fn find_longest_leading_edge_common_substring_length(s1: &str, s2: &str) -> i32 {
+// The NFC normalized text of the path to compare. Bytes that are not valid
+// UTF-8 are mapped onto private use characters, one per byte value, so unlike
+// a lossy conversion they still only match the same bytes.
+fn path_text(path: &Path) -> Cow<'_, str> {
+    match path.to_str() {
+        Some(s) => normalize(s),
+        None => Cow::Owned(normalize(&decode_non_utf8(path)).into_owned()),
+    }
+}
+
+#[cfg(unix)]
+fn decode_non_utf8(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut decoded = String::new();
+    for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+        decoded.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            decoded.push(char::from_u32(0x10FF00 + *byte as u32).unwrap());
+        }
+    }
+    decoded
+}
+
+#[cfg(not(unix))]
+fn decode_non_utf8(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// The default scoring strategy. It combines the leading edge similarity of
+/// the filenames, the overall similarity of the filenames and the similarity
+/// of the parent paths, each multiplied by its respective weight.
+///
+/// It can also score the similarity of the tokens of the filenames, split on
+/// `_`, `-`, `.` and camelCase boundaries, both in order and regardless of
+/// order. These token weights default to zero.
+///
+/// Similarity is measured in Unicode scalar values by default, or in
+/// grapheme clusters with `TextUnit::Grapheme`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimilarityScorer {
+    pub leading_edge_filename_weight: f32,
+    pub filename_weight: f32,
+    pub path_weight: f32,
+    pub token_filename_weight: f32,
+    pub unordered_token_filename_weight: f32,
+    pub text_unit: TextUnit,
+}
+
+impl SimilarityScorer {
+    pub fn new(leading_edge_filename_weight: f32, filename_weight: f32, path_weight: f32) -> Self {
+        SimilarityScorer {
+            leading_edge_filename_weight,
+            filename_weight,
+            path_weight,
+            token_filename_weight: 0.0,
+            unordered_token_filename_weight: 0.0,
+            text_unit: TextUnit::Char,
+        }
+    }
+
+    pub fn with_token_filename_weights(
+        mut self,
+        token_filename_weight: f32,
+        unordered_token_filename_weight: f32,
+    ) -> Self {
+        self.token_filename_weight = token_filename_weight;
+        self.unordered_token_filename_weight = unordered_token_filename_weight;
+        self
+    }
+
+    pub fn with_text_unit(mut self, text_unit: TextUnit) -> Self {
+        self.text_unit = text_unit;
+        self
+    }
+
+    /// The unweighted similarity of each of the components this scorer
+    /// scores, including the token components even when their weights are
+    /// zero.
+    pub fn components(&self, query_path: &Path, candidate_path: &Path) -> ScoreComponents {
+        let query_parts = PathParts::new(query_path);
+        let candidate_parts = PathParts::new(candidate_path);
+
+        let (leading_edge_filename, filename, path) =
+            parts_similarities(&query_parts, &candidate_parts, self.text_unit);
+        let (token_filename, unordered_token_filename) =
+            token_similarities(&query_parts, &candidate_parts);
+
+        ScoreComponents {
+            leading_edge_filename,
+            filename,
+            path,
+            token_filename,
+            unordered_token_filename,
+        }
+    }
+
+    /// The weighted contribution of each component to the total score.
+    pub fn weigh(&self, components: &ScoreComponents) -> ScoreComponents {
+        ScoreComponents {
+            leading_edge_filename: self.leading_edge_filename_weight
+                * components.leading_edge_filename,
+            filename: self.filename_weight * components.filename,
+            path: self.path_weight * components.path,
+            token_filename: self.token_filename_weight * components.token_filename,
+            unordered_token_filename: self.unordered_token_filename_weight
+                * components.unordered_token_filename,
+        }
+    }
+
+    fn scores_tokens(&self) -> bool {
+        self.token_filename_weight != 0.0 || self.unordered_token_filename_weight != 0.0
+    }
+
+    fn token_score(&self, query_parts: &PathParts, candidate_parts: &PathParts) -> f32 {
+        if !self.scores_tokens() {
+            return 0.0;
+        }
+
+        let (token_filename, unordered_token_filename) =
+            token_similarities(query_parts, candidate_parts);
+        (self.token_filename_weight * token_filename)
+            + (self.unordered_token_filename_weight * unordered_token_filename)
+    }
+}
+
+/// The similarity of each of the components of a candidate path to a query
+/// path, as computed by `SimilarityScorer`, either unweighted or weighted.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ScoreComponents {
+    pub leading_edge_filename: f32,
+    pub filename: f32,
+    pub path: f32,
+    pub token_filename: f32,
+    pub unordered_token_filename: f32,
+}
+
+impl ScoreComponents {
+    // summed in the same order as SimilarityScorer sums its score
+    pub fn total(&self) -> f32 {
+        (self.leading_edge_filename + self.filename + self.path)
+            + (self.token_filename + self.unordered_token_filename)
+    }
+}
+
+impl Default for SimilarityScorer {
+    fn default() -> Self {
+        SimilarityScorer::new(100.0, 10.0, 1.0)
+    }
+}
+
+impl Scorer for SimilarityScorer {
+    fn score(&self, query_path: &Path, candidate_path: &Path) -> f32 {
+        let parts = match self.scores_tokens() {
+            true => PathParts::new,
+            false => PathParts::without_tokens,
+        };
+        self.score_parts(
+            query_path,
+            &parts(query_path),
+            candidate_path,
+            &parts(candidate_path),
+        )
+    }
+
+    fn score_parts(
+        &self,
+        _query_path: &Path,
+        query_parts: &PathParts,
+        _candidate_path: &Path,
+        candidate_parts: &PathParts,
+    ) -> f32 {
+        let (leading_edge_filename_score, filename_score, path_score) =
+            parts_similarities(query_parts, candidate_parts, self.text_unit);
+
+        ((self.leading_edge_filename_weight * leading_edge_filename_score)
+            + (self.filename_weight * filename_score)
+            + (self.path_weight * path_score))
+            + self.token_score(query_parts, candidate_parts)
+    }
+}
+
+fn find_longest_common_substring_length<T: PartialEq>(s1: &[T], s2: &[T]) -> i32 {
+    find_longest_common_substring(s1, s2).0
+}
+
+// Find the length of the longest common substring along with where it starts
+// in each of s1 and s2. When there are several of the same length the first
+// one found in s1 is returned.
+fn find_longest_common_substring<T: PartialEq>(s1: &[T], s2: &[T]) -> (i32, usize, usize) {
+    let (length, s1_start, s2_start) = lcs::longest_common_substring(s1, s2);
+    (length as i32, s1_start, s2_start)
+}
+
+fn find_longest_leading_edge_common_substring_length<T: PartialEq>(s1: &[T], s2: &[T]) -> i32 {
     if s1.is_empty() || s2.is_empty() {
         return 0;
     }
 
-    let (shortest_bytes, longest_bytes) = if s1.len() < s2.len() {
-        (s1.as_bytes(), s2.as_bytes())
+    let (shortest, longest) = if s1.len() < s2.len() {
+        (s1, s2)
     } else {
-        (s2.as_bytes(), s1.as_bytes())
+        (s2, s1)
     };
 
     let mut longest_length = 0;
 
-    for i in 0..shortest_bytes.len() {
-        if shortest_bytes[i] == longest_bytes[i] {
+    for i in 0..shortest.len() {
+        if shortest[i] == longest[i] {
             longest_length += 1;
         } else {
             break;
@@ -74,7 +346,7 @@ fn find_longest_leading_edge_common_substring_length(s1: &str, s2: &str) -> i32
     longest_length
 }
 
-fn similarity_ratio(s1: &str, s2: &str) -> f32 {
+fn similarity_ratio<T: PartialEq>(s1: &[T], s2: &[T]) -> f32 {
     if s1.is_empty() || s2.is_empty() {
         return 0.0;
     }
@@ -85,7 +357,7 @@ fn similarity_ratio(s1: &str, s2: &str) -> f32 {
         * (longest_common_substring_len / s2.len() as f32)
 }
 
-fn leading_edge_similarity_ratio(s1: &str, s2: &str) -> f32 {
+fn leading_edge_similarity_ratio<T: PartialEq>(s1: &[T], s2: &[T]) -> f32 {
     if s1.is_empty() || s2.is_empty() {
         return 0.0;
     }
@@ -99,109 +371,257 @@ fn leading_edge_similarity_ratio(s1: &str, s2: &str) -> f32 {
     longest_common_substring_len / s1.len() as f32
 }
 
-fn score(
-    s1: &str,
-    s2: &str,
-    leading_edge_filename_weight: f32,
-    filename_weight: f32,
-    path_weight: f32,
-) -> f32 {
-    let path1 = Path::new(s1);
-    let path2 = Path::new(s2);
+/// Split a filename into lowercase tokens on `_`, `-`, `.`, whitespace and
+/// camelCase/PascalCase boundaries, e.g. `HTTPServer_test` becomes `http`,
+/// `server` and `test`.
+fn tokenize(s: &str) -> Vec<String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens: Vec<String> = vec![];
+    let mut token = String::new();
+
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' || *c == '-' || *c == '.' || c.is_whitespace() {
+            if !token.is_empty() {
+                tokens.push(token.to_lowercase());
+                token = String::new();
+            }
+            continue;
+        }
 
-    match (
-        path1.file_stem().and_then(|f| f.to_str()),
-        path2.file_stem().and_then(|f| f.to_str()),
-    ) {
+        if c.is_uppercase() && !token.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lowercase = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            // fooBar splits before B, HTTPServer splits before S
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lowercase)
+            {
+                tokens.push(token.to_lowercase());
+                token = String::new();
+            }
+        }
+
+        token.push(*c);
+    }
+
+    if !token.is_empty() {
+        tokens.push(token.to_lowercase());
+    }
+
+    tokens
+}
+
+/// Order aware token similarity, the token equivalent of `similarity_ratio`,
+/// based on the longest run of tokens the filenames have in common.
+fn token_similarity_ratio(t1: &[String], t2: &[String]) -> f32 {
+    if t1.is_empty() || t2.is_empty() {
+        return 0.0;
+    }
+
+    let longest_common_run_len = find_longest_common_substring_length(t1, t2) as f32;
+
+    (longest_common_run_len / t1.len() as f32) * (longest_common_run_len / t2.len() as f32)
+}
+
+/// Order insensitive token similarity based on how many tokens the filenames
+/// have in common, so `user_fee_submission` and `fee_submission_user` are
+/// identical.
+fn unordered_token_similarity_ratio(t1: &[String], t2: &[String]) -> f32 {
+    if t1.is_empty() || t2.is_empty() {
+        return 0.0;
+    }
+
+    let mut unmatched: Vec<&String> = t2.iter().collect();
+    let mut common_len = 0;
+    for token in t1.iter() {
+        if let Some(index) = unmatched.iter().position(|t| *t == token) {
+            unmatched.remove(index);
+            common_len += 1;
+        }
+    }
+
+    let common_len = common_len as f32;
+    (common_len / t1.len() as f32) * (common_len / t2.len() as f32)
+}
+
+fn token_similarities(parts1: &PathParts, parts2: &PathParts) -> (f32, f32) {
+    match (&parts1.stem, &parts2.stem) {
+        (Some(_), Some(_)) => (
+            token_similarity_ratio(&parts1.tokens, &parts2.tokens),
+            unordered_token_similarity_ratio(&parts1.tokens, &parts2.tokens),
+        ),
+        _ => (0.0, 0.0),
+    }
+}
+
+// The leading edge filename, filename and path similarities, all of which
+// are zero when either path has no filename.
+fn parts_similarities(
+    parts1: &PathParts,
+    parts2: &PathParts,
+    text_unit: TextUnit,
+) -> (f32, f32, f32) {
+    match (&parts1.stem, &parts2.stem) {
         (Some(path1_filename), Some(path2_filename)) => {
-            let filename_score = similarity_ratio(path1_filename, path2_filename);
+            let filename_score = text_unit.similarity_ratio(path1_filename, path2_filename);
             let leading_edge_filename_score =
-                leading_edge_similarity_ratio(path1_filename, path2_filename);
+                text_unit.leading_edge_similarity_ratio(path1_filename, path2_filename);
 
-            let path_score = match (
-                path1.parent().and_then(|f| f.to_str()),
-                path2.parent().and_then(|f| f.to_str()),
-            ) {
+            let path_score = match (&parts1.parent, &parts2.parent) {
                 (Some(path1_parent), Some(path2_parent)) => {
-                    similarity_ratio(path1_parent, path2_parent)
+                    text_unit.similarity_ratio(path1_parent, path2_parent)
                 }
                 (None, None) => 1.0, // both have no path, thats a perfect match
                 _ => 0.0,            // one has path but other doesn't, can't be any similarity
             };
 
-            // leading_edge_filename_score + filename_score + path_score
-            (leading_edge_filename_weight * leading_edge_filename_score)
-                + (filename_weight * filename_score)
-                + (path_weight * path_score)
+            (leading_edge_filename_score, filename_score, path_score)
         }
-        _ => 0.0,
+        _ => (0.0, 0.0, 0.0),
     }
 }
 
-pub fn score_paths(
-    paths: Vec<String>,
-    cleansed_path: &str,
-    leading_edge_filename_weight: f32,
-    filename_weight: f32,
-    path_weight: f32,
+/// Score a possible alternate path, or `None` when it is the query path
+/// itself.
+pub fn score_path<S: Scorer + ?Sized>(
+    path: &Path,
+    cleansed_path: &Path,
+    scorer: &S,
+) -> Option<ScoredPath> {
+    let path = cleanse_path(path);
+    if path == cleansed_path {
+        return None;
+    }
+    Some((scorer.score(cleansed_path, &path), path))
+}
+
+/// Score a candidate like `score_path`, using its parts and those of the
+/// query path when they were computed ahead of time.
+pub fn score_candidate<S: Scorer + ?Sized, C: Candidate + ?Sized>(
+    candidate: &C,
+    cleansed_path: &Path,
+    query_parts: &PathParts,
+    scorer: &S,
+) -> Option<ScoredPath> {
+    match candidate.parts() {
+        Some(candidate_parts) => {
+            let path = cleanse_path(candidate.path());
+            if path == cleansed_path {
+                return None;
+            }
+            let score = scorer.score_parts(cleansed_path, query_parts, &path, candidate_parts);
+            Some((score, path))
+        }
+        None => score_path(candidate.path(), cleansed_path, scorer),
+    }
+}
+
+pub fn score_paths<S: Scorer + ?Sized>(
+    paths: Vec<PathBuf>,
+    cleansed_path: &Path,
+    scorer: &S,
 ) -> Vec<ScoredPath> {
     paths
         .iter()
-        .map(|path| cleanse_path(path))
-        .filter(|path| path != cleansed_path)
-        .map(|path| {
-            (
-                score(
-                    cleansed_path,
-                    &path,
-                    leading_edge_filename_weight,
-                    filename_weight,
-                    path_weight,
-                ),
-                path,
-            )
-        })
+        .filter_map(|path| score_path(path, cleansed_path, scorer))
         .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        find_longest_leading_edge_common_substring_length, leading_edge_similarity_ratio, score,
-        score_paths, similarity_ratio,
+        chars, find_longest_common_substring, find_longest_common_substring_length, graphemes,
+        score_paths, token_similarity_ratio, tokenize, unordered_token_similarity_ratio, Scorer,
+        SimilarityScorer, TextUnit,
     };
+    use std::path::{Path, PathBuf};
+
+    fn score(
+        s1: &str,
+        s2: &str,
+        leading_edge_filename_weight: f32,
+        filename_weight: f32,
+        path_weight: f32,
+    ) -> f32 {
+        score_in(
+            s1,
+            s2,
+            leading_edge_filename_weight,
+            filename_weight,
+            path_weight,
+            TextUnit::Char,
+        )
+    }
+
+    fn score_in(
+        s1: &str,
+        s2: &str,
+        leading_edge_filename_weight: f32,
+        filename_weight: f32,
+        path_weight: f32,
+        text_unit: TextUnit,
+    ) -> f32 {
+        SimilarityScorer::new(leading_edge_filename_weight, filename_weight, path_weight)
+            .with_text_unit(text_unit)
+            .score(Path::new(s1), Path::new(s2))
+    }
+
+    // The similarities of strings compared by char, as they are by default.
+    fn similarity_ratio(s1: &str, s2: &str) -> f32 {
+        super::similarity_ratio(&chars(s1), &chars(s2))
+    }
+
+    fn leading_edge_similarity_ratio(s1: &str, s2: &str) -> f32 {
+        super::leading_edge_similarity_ratio(&chars(s1), &chars(s2))
+    }
+
+    fn find_longest_leading_edge_common_substring_length(s1: &str, s2: &str) -> i32 {
+        super::find_longest_leading_edge_common_substring_length(&chars(s1), &chars(s2))
+    }
+
+    fn grapheme_similarity_ratio(s1: &str, s2: &str) -> f32 {
+        super::similarity_ratio(&graphemes(s1), &graphemes(s2))
+    }
 
     #[test]
     fn score_paths_with_same_path_it_should_filter_same_path() {
-        let paths: Vec<String> = vec![
-            "foo/bar/car.ts".to_owned(),
-            "hoopty/doopty/foopty.ts".to_owned(),
-            "home/away/lets_play.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "foo/bar/car.ts".into(),
+            "hoopty/doopty/foopty.ts".into(),
+            "home/away/lets_play.ts".into(),
         ];
 
-        let scored_paths = score_paths(paths, "hoopty/doopty/foopty.ts", 100.0, 10.0, 1.0);
+        let scored_paths = score_paths(
+            paths,
+            Path::new("hoopty/doopty/foopty.ts"),
+            &SimilarityScorer::default(),
+        );
 
         assert_eq!(scored_paths.len(), 2);
-        assert_eq!(scored_paths[0].1, "foo/bar/car.ts".to_owned());
-        assert_eq!(scored_paths[1].1, "home/away/lets_play.ts".to_owned());
+        assert_eq!(scored_paths[0].1, Path::new("foo/bar/car.ts"));
+        assert_eq!(scored_paths[1].1, Path::new("home/away/lets_play.ts"));
         assert!(scored_paths[0].0 > 0.0);
         assert!(scored_paths[1].0 > 0.0);
     }
 
     #[test]
     fn score_paths_without_same_path_it_should_not_filter_same_path() {
-        let paths: Vec<String> = vec![
-            "foo/bar/car.ts".to_owned(),
-            "hoopty/doopty/foopty.ts".to_owned(),
-            "home/away/lets_play.ts".to_owned(),
+        let paths: Vec<PathBuf> = vec![
+            "foo/bar/car.ts".into(),
+            "hoopty/doopty/foopty.ts".into(),
+            "home/away/lets_play.ts".into(),
         ];
 
-        let scored_paths = score_paths(paths, "person/place/thing.ts", 100.0, 10.0, 1.0);
+        let scored_paths = score_paths(
+            paths,
+            Path::new("person/place/thing.ts"),
+            &SimilarityScorer::default(),
+        );
 
         assert_eq!(scored_paths.len(), 3);
-        assert_eq!(scored_paths[0].1, "foo/bar/car.ts".to_owned());
-        assert_eq!(scored_paths[1].1, "hoopty/doopty/foopty.ts".to_owned());
-        assert_eq!(scored_paths[2].1, "home/away/lets_play.ts".to_owned());
+        assert_eq!(scored_paths[0].1, Path::new("foo/bar/car.ts"));
+        assert_eq!(scored_paths[1].1, Path::new("hoopty/doopty/foopty.ts"));
+        assert_eq!(scored_paths[2].1, Path::new("home/away/lets_play.ts"));
         assert!(scored_paths[0].0 > 0.0);
         assert!(scored_paths[1].0 > 0.0);
         assert!(scored_paths[2].0 > 0.0);
@@ -262,6 +682,41 @@ mod tests {
         assert!(val_b > val_a);
     }
 
+    #[test]
+    fn similarity_scorer_scores_using_its_weights() {
+        let scorer = SimilarityScorer::new(1.0, 1.0, 10.0);
+        let val = scorer.score(Path::new("foo/bar/car.ts"), Path::new("aaa/ddd/car.ts"));
+        assert_eq!(
+            val,
+            score("foo/bar/car.ts", "aaa/ddd/car.ts", 1.0, 1.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn similarity_scorer_defaults_to_the_standard_weights() {
+        assert_eq!(
+            SimilarityScorer::default(),
+            SimilarityScorer::new(100.0, 10.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn score_paths_uses_the_provided_scorer() {
+        struct LengthScorer;
+
+        impl Scorer for LengthScorer {
+            fn score(&self, _query_path: &Path, candidate_path: &Path) -> f32 {
+                candidate_path.as_os_str().len() as f32
+            }
+        }
+
+        let paths: Vec<PathBuf> = vec!["a/b.ts".into(), "./aa/bb.ts".into()];
+        let scored_paths = score_paths(paths, Path::new("c/d.ts"), &LengthScorer);
+
+        assert_eq!(scored_paths[0], (6.0, PathBuf::from("a/b.ts")));
+        assert_eq!(scored_paths[1], (8.0, PathBuf::from("aa/bb.ts")));
+    }
+
     #[test]
     fn similarity_ratio_is_zero_when_one_or_more_strings_is_empty() {
         assert_eq!(similarity_ratio("", "foo/bar/car.ts"), 0.0);
@@ -331,4 +786,234 @@ mod tests {
         let similarity_ratio = leading_edge_similarity_ratio("hellogoody", "helloworldhelloagain");
         assert_eq!(similarity_ratio, 0.5);
     }
+
+    #[test]
+    fn tokenize_splits_on_separators() {
+        assert_eq!(
+            tokenize("user_fee-submission.test"),
+            vec!["user", "fee", "submission", "test"]
+        );
+    }
+
+    #[test]
+    fn tokenize_splits_on_camel_case_boundaries() {
+        assert_eq!(
+            tokenize("userFeeSubmission"),
+            vec!["user", "fee", "submission"]
+        );
+        assert_eq!(
+            tokenize("UserFeeSubmission"),
+            vec!["user", "fee", "submission"]
+        );
+        assert_eq!(tokenize("HTTPServerTest"), vec!["http", "server", "test"]);
+        assert_eq!(tokenize("oauth2Client"), vec!["oauth2", "client"]);
+    }
+
+    #[test]
+    fn tokenize_ignores_repeated_separators() {
+        assert_eq!(tokenize("__init__"), vec!["init"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn token_similarity_ratio_is_zero_when_one_or_more_is_empty() {
+        assert_eq!(
+            token_similarity_ratio(&tokenize(""), &tokenize("foo_bar")),
+            0.0
+        );
+        assert_eq!(
+            token_similarity_ratio(&tokenize("foo_bar"), &tokenize("")),
+            0.0
+        );
+    }
+
+    #[test]
+    fn token_similarity_ratio_respects_token_order() {
+        let val = token_similarity_ratio(
+            &tokenize("user_fee_submission"),
+            &tokenize("fee_submission_user"),
+        );
+        assert_eq!(val, (2.0 / 3.0) * (2.0 / 3.0));
+    }
+
+    #[test]
+    fn token_similarity_ratio_matches_across_naming_styles() {
+        let val = token_similarity_ratio(
+            &tokenize("user_fee_submission"),
+            &tokenize("UserFeeSubmission"),
+        );
+        assert_eq!(val, 1.0);
+    }
+
+    #[test]
+    fn unordered_token_similarity_ratio_ignores_token_order() {
+        let val = unordered_token_similarity_ratio(
+            &tokenize("user_fee_submission"),
+            &tokenize("fee_submission_user"),
+        );
+        assert_eq!(val, 1.0);
+    }
+
+    #[test]
+    fn unordered_token_similarity_ratio_counts_repeated_tokens_once_per_match() {
+        let val = unordered_token_similarity_ratio(&tokenize("user_user"), &tokenize("user_spec"));
+        assert_eq!(val, 0.5 * 0.5);
+    }
+
+    #[test]
+    fn similarity_scorer_ignores_tokens_by_default() {
+        let scorer = SimilarityScorer::default();
+        assert_eq!(
+            scorer.score(Path::new("app/user_fee.rb"), Path::new("app/fee_user.rb")),
+            score("app/user_fee.rb", "app/fee_user.rb", 100.0, 10.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn similarity_scorer_blends_in_token_scores() {
+        let scorer = SimilarityScorer::new(0.0, 0.0, 0.0).with_token_filename_weights(1.0, 10.0);
+        let val = scorer.score(
+            Path::new("spec/user_fee_submission_spec.rb"),
+            Path::new("app/fee_submission_user.rb"),
+        );
+        assert_eq!(
+            val,
+            (1.0 * (2.0 / 4.0) * (2.0 / 3.0)) + (10.0 * (3.0 / 4.0))
+        );
+    }
+
+    #[test]
+    fn similarity_scorer_with_token_weights_prefers_reordered_tokens() {
+        let scorer = SimilarityScorer::new(0.0, 10.0, 0.0).with_token_filename_weights(0.0, 10.0);
+        let val_a = scorer.score(
+            Path::new("user_fee_submission.rb"),
+            Path::new("fee_submission_user.rb"),
+        );
+        let val_b = scorer.score(
+            Path::new("user_fee_submission.rb"),
+            Path::new("user_fee_payment.rb"),
+        );
+        assert!(val_a > val_b);
+    }
+
+    #[test]
+    fn similarity_ratio_counts_chars_rather_than_bytes() {
+        let val_a = similarity_ratio("ユーザー", "ユーザー設定");
+        let val_b = similarity_ratio("abcd", "abcdef");
+        assert_eq!(val_a, val_b);
+    }
+
+    #[test]
+    fn find_longest_common_substring_length_does_not_match_partial_chars() {
+        // é (U+00E9) and ê (U+00EA) share their leading UTF-8 byte
+        assert_eq!(
+            find_longest_common_substring_length(&chars("é"), &chars("ê")),
+            0
+        );
+        assert_eq!(
+            find_longest_leading_edge_common_substring_length("é", "ê"),
+            0
+        );
+    }
+
+    #[test]
+    fn scorer_treats_composed_and_decomposed_paths_as_identical() {
+        let scorer = SimilarityScorer::default();
+        let composed = scorer.score(
+            Path::new("docs/caf\u{e9}.md"),
+            Path::new("docs/caf\u{e9}.md"),
+        );
+        let decomposed = scorer.score(
+            Path::new("docs/caf\u{e9}.md"),
+            Path::new("docs/cafe\u{301}.md"),
+        );
+        assert_eq!(composed, decomposed);
+    }
+
+    #[test]
+    fn scorer_ranks_non_ascii_filenames_by_char_similarity() {
+        let scorer = SimilarityScorer::default();
+        let val_a = scorer.score(
+            Path::new("資料/ユーザー.rb"),
+            Path::new("資料/ユーザー_spec.rb"),
+        );
+        let val_b = scorer.score(
+            Path::new("資料/ユーザー.rb"),
+            Path::new("資料/ユニット_spec.rb"),
+        );
+        assert!(val_a > val_b);
+    }
+
+    #[test]
+    fn grapheme_similarity_only_matches_whole_clusters() {
+        let family_with_girl = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let family_with_boy = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f466}";
+
+        assert!(similarity_ratio(family_with_girl, family_with_boy) > 0.0);
+        assert_eq!(
+            grapheme_similarity_ratio(family_with_girl, family_with_boy),
+            0.0
+        );
+    }
+
+    #[test]
+    fn scorer_with_grapheme_text_unit_scores_in_graphemes() {
+        // two graphemes, one of which is shared, but six chars of which five are
+        let val = score_in(
+            "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}x.png",
+            "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}y.png",
+            0.0,
+            1.0,
+            0.0,
+            TextUnit::Grapheme,
+        );
+        assert_eq!(val, 0.25);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn scorer_distinguishes_bytes_that_are_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let scorer = SimilarityScorer::default();
+        let query_path = Path::new(OsStr::from_bytes(b"app/caf\xe9.rb"));
+        let val_a = scorer.score(query_path, Path::new(OsStr::from_bytes(b"lib/caf\xe9.rb")));
+        let val_b = scorer.score(query_path, Path::new(OsStr::from_bytes(b"lib/caf\xea.rb")));
+        assert!(val_a > val_b);
+    }
+
+    #[test]
+    fn similarity_scorer_score_is_the_total_of_its_weighted_components() {
+        let scorer = SimilarityScorer::default().with_token_filename_weights(3.0, 4.0);
+        let query_path = Path::new("spec/models/user_fee_spec.rb");
+        let candidate_path = Path::new("app/models/fee_user.rb");
+
+        let components = scorer.components(query_path, candidate_path);
+        assert!(components.unordered_token_filename > 0.0);
+        assert_eq!(
+            scorer.weigh(&components).total(),
+            scorer.score(query_path, candidate_path)
+        );
+    }
+
+    #[test]
+    fn similarity_scorer_components_include_tokens_when_their_weights_are_zero() {
+        let components = SimilarityScorer::default()
+            .components(Path::new("app/user_fee.rb"), Path::new("app/fee_user.rb"));
+        assert_eq!(components.unordered_token_filename, 1.0);
+        assert_eq!(components.path, 1.0);
+    }
+
+    #[test]
+    fn find_longest_common_substring_returns_where_it_starts_in_each() {
+        assert_eq!(
+            find_longest_common_substring(&chars("spec/models"), &chars("app/models")),
+            (7, 4, 3)
+        );
+        assert_eq!(
+            find_longest_common_substring(&chars("abc"), &chars("xyz")),
+            (0, 0, 0)
+        );
+    }
 }
@@ -1,26 +1,130 @@
-pub fn cleanse_path(path: &str) -> String {
-    let s = path.to_string();
-    if s.len() > 1 && s[0..2].to_string() == "./" {
-        s[2..].to_string()
-    } else {
-        s
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+pub fn cleanse_path(path: &Path) -> PathBuf {
+    path.strip_prefix(".").unwrap_or(path).to_path_buf()
+}
+
+/// Build a path from the raw bytes it is made of, e.g. a line read from a
+/// file, without requiring them to be valid UTF-8.
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The raw bytes of the given path, so that it can be output exactly as it
+/// exists on disk.
+#[cfg(unix)]
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+/// A path that can be serialized, as text when it is valid UTF-8 and as its
+/// raw bytes otherwise, so that paths which are not survive being written as
+/// JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SerializedPath {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl SerializedPath {
+    pub fn new(path: &Path) -> Self {
+        match path.to_str() {
+            Some(s) => SerializedPath::Text(s.to_string()),
+            None => SerializedPath::Bytes(path_to_bytes(path).into_owned()),
+        }
+    }
+
+    pub fn to_path_buf(&self) -> PathBuf {
+        match self {
+            SerializedPath::Text(s) => PathBuf::from(s),
+            SerializedPath::Bytes(bytes) => path_from_bytes(bytes.clone()),
+        }
     }
 }
 
+/// Hash the given parts with FNV-1a. Unlike the standard library's hashers
+/// it gives the same hash from one build to the next, so it can be used to
+/// name files that have to be found again, e.g. by later runs.
+pub fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in *part {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        // separate the parts so that ["ab", "c"] and ["a", "bc"] differ
+        hash ^= 0xff;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 #[cfg(test)]
 mod tests {
-    use super::cleanse_path;
+    use super::{cleanse_path, fnv1a, path_from_bytes, path_to_bytes, SerializedPath};
+    use serde_json;
+    use std::path::Path;
 
     #[test]
     fn cleanse_path_returns_path_with_dot_slash_prefix_stripped() {
-        assert_eq!("hoopty/doopty.thing", cleanse_path("./hoopty/doopty.thing"));
+        assert_eq!(
+            Path::new("hoopty/doopty.thing"),
+            cleanse_path(Path::new("./hoopty/doopty.thing"))
+        );
     }
 
     #[test]
     fn cleanse_path_does_not_effect_non_dot_slash_prefixes() {
         assert_eq!(
-            "foo/hoopty/doopty.thing",
-            cleanse_path("foo/hoopty/doopty.thing")
+            Path::new("foo/hoopty/doopty.thing"),
+            cleanse_path(Path::new("foo/hoopty/doopty.thing"))
+        );
+        assert_eq!(
+            Path::new(".hidden/doopty.thing"),
+            cleanse_path(Path::new(".hidden/doopty.thing"))
         );
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn path_bytes_round_trip_when_not_utf8() {
+        let bytes = b"foo/caf\xe9.rb".to_vec();
+        let path = path_from_bytes(bytes.clone());
+        assert_eq!(path_to_bytes(&path).into_owned(), bytes);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn serialized_path_round_trips_through_json_when_not_utf8() {
+        let path = path_from_bytes(b"foo/caf\xe9.rb".to_vec());
+        let json = serde_json::to_string(&SerializedPath::new(&path)).unwrap();
+        let serialized: SerializedPath = serde_json::from_str(&json).unwrap();
+        assert_eq!(serialized.to_path_buf(), path);
+    }
+
+    #[test]
+    fn fnv1a_hashes_differ_by_where_the_parts_split() {
+        assert_ne!(fnv1a(&[b"ab", b"c"]), fnv1a(&[b"a", b"bc"]));
+    }
 }
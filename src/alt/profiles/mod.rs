@@ -0,0 +1,336 @@
+use alt::rules::Rule;
+use std::path::Path;
+
+/// A named set of rules encoding the test naming and directory layout
+/// conventions of a language or framework.
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    /// The names the profile can be selected by, the first being canonical.
+    pub names: &'static [&'static str],
+    /// Files whose presence in the project root indicate the convention is
+    /// in use.
+    pub markers: &'static [&'static str],
+    /// The rules as (pattern, template) pairs, see `Rule` for the syntax.
+    pub rules: &'static [(&'static str, &'static str)],
+}
+
+impl Profile {
+    pub fn name(&self) -> &'static str {
+        self.names[0]
+    }
+
+    pub fn compile_rules(&self) -> Vec<Rule> {
+        self.rules
+            .iter()
+            .map(|(pattern, template)| {
+                Rule::new(pattern, template).expect("built-in profile rules are valid")
+            })
+            .collect()
+    }
+}
+
+const RAILS_RULES: &[(&str, &str)] = &[
+    ("spec/lib/**/*_spec.rb", "lib/{1}/{2}.rb"),
+    ("spec/**/*_spec.rb", "app/{1}/{2}.rb"),
+    ("test/lib/**/*_test.rb", "lib/{1}/{2}.rb"),
+    ("test/**/*_test.rb", "app/{1}/{2}.rb"),
+    ("app/**/*.rb", "spec/{1}/{2}_spec.rb"),
+    ("app/**/*.rb", "test/{1}/{2}_test.rb"),
+    ("lib/**/*.rb", "spec/lib/{1}/{2}_spec.rb"),
+    ("lib/**/*.rb", "test/lib/{1}/{2}_test.rb"),
+];
+
+const RSPEC_RULES: &[(&str, &str)] = &[
+    ("spec/**/*_spec.rb", "lib/{1}/{2}.rb"),
+    ("lib/**/*.rb", "spec/{1}/{2}_spec.rb"),
+];
+
+const GO_RULES: &[(&str, &str)] = &[
+    ("**/*_test.go", "{1}/{2}.go"),
+    ("**/*.go", "{1}/{2}_test.go"),
+];
+
+const RUST_RULES: &[(&str, &str)] = &[
+    ("tests/**/*.rs", "src/{1}/{2}.rs"),
+    ("tests/**/*.rs", "src/{1}/{2}/mod.rs"),
+    ("src/**/*/mod.rs", "tests/{1}/{2}.rs"),
+    ("src/**/*.rs", "tests/{1}/{2}.rs"),
+];
+
+const JEST_RULES: &[(&str, &str)] = &[
+    ("**/__tests__/*.test.*", "{1}/{2}.{3}"),
+    ("**/__tests__/*.spec.*", "{1}/{2}.{3}"),
+    ("**/*.test.*", "{1}/{2}.{3}"),
+    ("**/*.spec.*", "{1}/{2}.{3}"),
+    ("**/*.js", "{1}/{2}.test.js"),
+    ("**/*.js", "{1}/__tests__/{2}.test.js"),
+    ("**/*.js", "{1}/{2}.spec.js"),
+    ("**/*.jsx", "{1}/{2}.test.jsx"),
+    ("**/*.jsx", "{1}/__tests__/{2}.test.jsx"),
+    ("**/*.jsx", "{1}/{2}.spec.jsx"),
+    ("**/*.ts", "{1}/{2}.test.ts"),
+    ("**/*.ts", "{1}/__tests__/{2}.test.ts"),
+    ("**/*.ts", "{1}/{2}.spec.ts"),
+    ("**/*.tsx", "{1}/{2}.test.tsx"),
+    ("**/*.tsx", "{1}/__tests__/{2}.test.tsx"),
+    ("**/*.tsx", "{1}/{2}.spec.tsx"),
+];
+
+const PYTEST_RULES: &[(&str, &str)] = &[
+    ("tests/**/test_*.py", "{1}/{2}.py"),
+    ("tests/**/test_*.py", "src/{1}/{2}.py"),
+    ("tests/**/*_test.py", "{1}/{2}.py"),
+    ("**/test_*.py", "{1}/{2}.py"),
+    ("**/*_test.py", "{1}/{2}.py"),
+    ("**/*.py", "tests/{1}/test_{2}.py"),
+    ("**/*.py", "{1}/test_{2}.py"),
+    ("src/**/*.py", "tests/{1}/test_{2}.py"),
+];
+
+const JVM_RULES: &[(&str, &str)] = &[
+    ("src/test/java/**/*Test.java", "src/main/java/{1}/{2}.java"),
+    ("src/test/java/**/*Tests.java", "src/main/java/{1}/{2}.java"),
+    ("src/main/java/**/*.java", "src/test/java/{1}/{2}Test.java"),
+    ("src/main/java/**/*.java", "src/test/java/{1}/{2}Tests.java"),
+    ("src/test/kotlin/**/*Test.kt", "src/main/kotlin/{1}/{2}.kt"),
+    ("src/main/kotlin/**/*.kt", "src/test/kotlin/{1}/{2}Test.kt"),
+];
+
+const C_RULES: &[(&str, &str)] = &[
+    ("include/**/*.h", "src/{1}/{2}.c"),
+    ("include/**/*.h", "src/{1}/{2}.cpp"),
+    ("include/**/*.hpp", "src/{1}/{2}.cpp"),
+    ("src/**/*.c", "include/{1}/{2}.h"),
+    ("src/**/*.cpp", "include/{1}/{2}.hpp"),
+    ("src/**/*.cpp", "include/{1}/{2}.h"),
+    ("**/*.h", "{1}/{2}.c"),
+    ("**/*.h", "{1}/{2}.cpp"),
+    ("**/*.h", "{1}/{2}.cc"),
+    ("**/*.hpp", "{1}/{2}.cpp"),
+    ("**/*.hh", "{1}/{2}.cc"),
+    ("**/*.c", "{1}/{2}.h"),
+    ("**/*.cpp", "{1}/{2}.hpp"),
+    ("**/*.cpp", "{1}/{2}.h"),
+    ("**/*.cc", "{1}/{2}.hh"),
+    ("**/*.cc", "{1}/{2}.h"),
+];
+
+const ELIXIR_RULES: &[(&str, &str)] = &[
+    ("test/**/*_test.exs", "lib/{1}/{2}.ex"),
+    ("lib/**/*.ex", "test/{1}/{2}_test.exs"),
+];
+
+/// The built-in profiles, in the order their rules are evaluated when more
+/// than one is in use.
+pub const PROFILES: &[Profile] = &[
+    Profile {
+        names: &["rails"],
+        markers: &["Gemfile"],
+        rules: RAILS_RULES,
+    },
+    Profile {
+        names: &["rspec"],
+        markers: &[".rspec"],
+        rules: RSPEC_RULES,
+    },
+    Profile {
+        names: &["go"],
+        markers: &["go.mod"],
+        rules: GO_RULES,
+    },
+    Profile {
+        names: &["rust"],
+        markers: &["Cargo.toml"],
+        rules: RUST_RULES,
+    },
+    Profile {
+        names: &["jest"],
+        markers: &["package.json"],
+        rules: JEST_RULES,
+    },
+    Profile {
+        names: &["pytest"],
+        markers: &[
+            "pytest.ini",
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "tox.ini",
+            "conftest.py",
+        ],
+        rules: PYTEST_RULES,
+    },
+    Profile {
+        names: &["maven", "gradle"],
+        markers: &["pom.xml", "build.gradle", "build.gradle.kts"],
+        rules: JVM_RULES,
+    },
+    Profile {
+        names: &["c", "cpp"],
+        markers: &["CMakeLists.txt", "meson.build", "Makefile"],
+        rules: C_RULES,
+    },
+    Profile {
+        names: &["elixir"],
+        markers: &["mix.exs"],
+        rules: ELIXIR_RULES,
+    },
+];
+
+pub fn find_profile(name: &str) -> Option<&'static Profile> {
+    PROFILES
+        .iter()
+        .find(|profile| profile.names.contains(&name))
+}
+
+/// Detect the profiles in use based on the marker files present in `dir`.
+pub fn detect_profiles(dir: &Path) -> Vec<&'static Profile> {
+    PROFILES
+        .iter()
+        .filter(|profile| {
+            profile
+                .markers
+                .iter()
+                .any(|marker| dir.join(marker).is_file())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{detect_profiles, find_profile, Profile, PROFILES};
+    use alt::rules::project;
+    use std::fs;
+
+    fn projections(profile_name: &str, path: &str) -> Vec<String> {
+        let profile: &Profile = find_profile(profile_name).unwrap();
+        project(&profile.compile_rules(), path)
+    }
+
+    #[test]
+    fn all_profiles_have_valid_rules() {
+        for profile in PROFILES.iter() {
+            assert_eq!(profile.compile_rules().len(), profile.rules.len());
+        }
+    }
+
+    #[test]
+    fn find_profile_finds_profiles_by_any_of_their_names() {
+        assert_eq!(find_profile("gradle").unwrap().name(), "maven");
+        assert_eq!(find_profile("cpp").unwrap().name(), "c");
+        assert!(find_profile("cobol").is_none());
+    }
+
+    #[test]
+    fn rails_profile_maps_specs_and_implementations() {
+        assert_eq!(
+            projections("rails", "spec/queues/fee/user_fee_submission_spec.rb")[0],
+            "app/queues/fee/user_fee_submission.rb"
+        );
+        assert_eq!(
+            projections("rails", "spec/lib/foo/bar_spec.rb")[0],
+            "lib/foo/bar.rb"
+        );
+        assert_eq!(
+            projections("rails", "app/models/user.rb")[0],
+            "spec/models/user_spec.rb"
+        );
+    }
+
+    #[test]
+    fn go_profile_maps_tests_and_implementations() {
+        assert_eq!(projections("go", "pkg/foo_test.go")[0], "pkg/foo.go");
+        assert_eq!(projections("go", "pkg/foo.go")[0], "pkg/foo_test.go");
+    }
+
+    #[test]
+    fn rust_profile_maps_integration_tests_and_modules() {
+        assert_eq!(
+            projections("rust", "tests/parser.rs"),
+            vec!["src/parser.rs", "src/parser/mod.rs"]
+        );
+        assert_eq!(
+            projections("rust", "src/parser/mod.rs")[0],
+            "tests/parser.rs"
+        );
+    }
+
+    #[test]
+    fn jest_profile_maps_tests_and_implementations() {
+        assert_eq!(
+            projections("jest", "src/__tests__/foo.test.ts")[0],
+            "src/foo.ts"
+        );
+        assert_eq!(projections("jest", "src/foo.spec.tsx")[0], "src/foo.tsx");
+        assert_eq!(
+            projections("jest", "src/foo.ts"),
+            vec![
+                "src/foo.test.ts",
+                "src/__tests__/foo.test.ts",
+                "src/foo.spec.ts"
+            ]
+        );
+    }
+
+    #[test]
+    fn pytest_profile_maps_tests_and_implementations() {
+        assert_eq!(projections("pytest", "tests/test_foo.py")[0], "foo.py");
+        assert_eq!(
+            projections("pytest", "tests/pkg/test_foo.py")[0],
+            "pkg/foo.py"
+        );
+        assert_eq!(projections("pytest", "foo.py")[0], "tests/test_foo.py");
+    }
+
+    #[test]
+    fn maven_profile_mirrors_main_and_test_directories() {
+        assert_eq!(
+            projections("maven", "src/test/java/com/acme/FooTest.java")[0],
+            "src/main/java/com/acme/Foo.java"
+        );
+        assert_eq!(
+            projections("gradle", "src/main/java/com/acme/Foo.java")[0],
+            "src/test/java/com/acme/FooTest.java"
+        );
+    }
+
+    #[test]
+    fn c_profile_maps_headers_and_sources() {
+        assert_eq!(projections("c", "include/foo/bar.h")[0], "src/foo/bar.c");
+        assert_eq!(projections("cpp", "lib/bar.hpp")[0], "lib/bar.cpp");
+        assert!(projections("c", "lib/bar.cc").contains(&"lib/bar.h".to_string()));
+    }
+
+    #[test]
+    fn elixir_profile_mirrors_lib_and_test_directories() {
+        assert_eq!(
+            projections("elixir", "test/my_app/user_test.exs")[0],
+            "lib/my_app/user.ex"
+        );
+        assert_eq!(
+            projections("elixir", "lib/my_app/user.ex")[0],
+            "test/my_app/user_test.exs"
+        );
+    }
+
+    #[test]
+    fn detect_profiles_uses_marker_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("go.mod"), "").unwrap();
+        fs::write(dir.path().join("Gemfile"), "").unwrap();
+
+        let names: Vec<&str> = detect_profiles(dir.path())
+            .iter()
+            .map(|profile| profile.name())
+            .collect();
+        assert_eq!(names, vec!["rails", "go"]);
+    }
+
+    #[test]
+    fn detect_profiles_with_no_markers_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(detect_profiles(dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+use alt::path::scoring::ScoredPath;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::mem;
+use std::path::PathBuf;
+
+/// Collects scored paths as they are scored and ranks them by score, with
+/// ties ranked in the order the paths were given in, and then by path. Paths
+/// produced in no particular order, e.g. by a parallel directory walk, can
+/// all be given the same position so that ties are ranked by path. Paths
+/// that are projections of the query path by a rule are ranked first, in the
+/// order of the projections.
+///
+/// When limited to the top `limit` paths only that many are ever held, in a
+/// heap, rather than sorting every scored path and then truncating.
+pub struct Ranking {
+    limit: usize,
+    projections: Vec<PathBuf>,
+    projected: Vec<Option<Entry>>,
+    rest: Rest,
+}
+
+enum Rest {
+    All(Vec<Entry>),
+    Top(BinaryHeap<Entry>),
+}
+
+// A scored path along with the position of the path in the input, which,
+// followed by the path, breaks ties between equal scores.
+struct Entry {
+    score: f32,
+    index: usize,
+    path: PathBuf,
+}
+
+// Entries are ordered best first, so the top of the max-heap is the worst of
+// the entries kept.
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        order_scores(self.score, other.score)
+            .then(self.index.cmp(&other.index))
+            .then_with(|| self.path.cmp(&other.path))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+// Higher scores first, in a total order even when a scorer gives NaN, which
+// the heap and sorting rely on.
+fn order_scores(score_a: f32, score_b: f32) -> Ordering {
+    score_b.total_cmp(&score_a)
+}
+
+impl Ranking {
+    /// Create a ranking of at most `limit` paths, or of every path when
+    /// `limit` is 0, that ranks the given projections first.
+    pub fn new(limit: usize, projections: Vec<PathBuf>) -> Self {
+        let rest = match limit {
+            0 => Rest::All(vec![]),
+            _ => Rest::Top(BinaryHeap::with_capacity(limit + 1)),
+        };
+        Ranking {
+            limit,
+            projected: projections.iter().map(|_| None).collect(),
+            projections,
+            rest,
+        }
+    }
+
+    /// Add the scored path found at `index` in the input.
+    pub fn push(&mut self, index: usize, scored_path: ScoredPath) {
+        let (score, path) = scored_path;
+        self.push_entry(Entry { score, index, path });
+    }
+
+    fn push_entry(&mut self, entry: Entry) {
+        let entry = match self.projections.iter().position(|p| *p == entry.path) {
+            // keep the best of any duplicates of a projection for its slot
+            Some(slot) => match self.projected[slot].take() {
+                Some(projected) if projected <= entry => {
+                    self.projected[slot] = Some(projected);
+                    entry
+                }
+                Some(projected) => {
+                    self.projected[slot] = Some(entry);
+                    projected
+                }
+                None => {
+                    self.projected[slot] = Some(entry);
+                    return;
+                }
+            },
+            None => entry,
+        };
+
+        match self.rest {
+            Rest::All(ref mut entries) => entries.push(entry),
+            Rest::Top(ref mut heap) => {
+                if heap.len() < self.limit {
+                    heap.push(entry);
+                } else if heap.peek().is_some_and(|worst| entry < *worst) {
+                    heap.pop();
+                    heap.push(entry);
+                }
+            }
+        }
+    }
+
+    /// Merge a ranking of other paths from the same input, e.g. one built by
+    /// another thread, into this one.
+    pub fn merge(&mut self, other: Ranking) {
+        for entry in other.projected.into_iter().flatten() {
+            self.push_entry(entry);
+        }
+        let entries = match other.rest {
+            Rest::All(entries) => entries,
+            Rest::Top(heap) => heap.into_vec(),
+        };
+        for entry in entries {
+            self.push_entry(entry);
+        }
+    }
+
+    /// The ranked scored paths, best first.
+    pub fn into_scored_paths(mut self) -> Vec<ScoredPath> {
+        let mut entries: Vec<Entry> = match mem::replace(&mut self.rest, Rest::All(vec![])) {
+            Rest::All(entries) => entries,
+            Rest::Top(heap) => heap.into_vec(),
+        };
+        entries.sort();
+
+        let mut scored_paths: Vec<ScoredPath> = self
+            .projected
+            .into_iter()
+            .flatten()
+            .chain(entries)
+            .map(|entry| (entry.score, entry.path))
+            .collect();
+        if self.limit > 0 {
+            scored_paths.truncate(self.limit);
+        }
+        scored_paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{order_scores, Ranking};
+    use alt::path::scoring::ScoredPath;
+    use std::cmp::Ordering;
+    use std::path::PathBuf;
+
+    fn rank(limit: usize, projections: &[&str], scored_paths: &[ScoredPath]) -> Vec<ScoredPath> {
+        let mut ranking = Ranking::new(limit, projections.iter().map(PathBuf::from).collect());
+        for (index, scored_path) in scored_paths.iter().enumerate() {
+            ranking.push(index, scored_path.clone());
+        }
+        ranking.into_scored_paths()
+    }
+
+    fn scored_paths() -> Vec<ScoredPath> {
+        vec![
+            (0.2, "some/other_path/to/a/bar.ts".into()),
+            (0.8, "some/path/to/a/file.ts".into()),
+            (0.023, "some/blue/fortytwo/sports_ball.ts".into()),
+            (0.4, "some/path/to/another/foo.ts".into()),
+            (0.1, "some/short/path/zoo.ts".into()),
+        ]
+    }
+
+    #[test]
+    fn order_scores_with_a_larger() {
+        assert_eq!(order_scores(0.3, 0.2), Ordering::Less);
+    }
+
+    #[test]
+    fn order_scores_with_a_smaller() {
+        assert_eq!(order_scores(0.2, 0.3), Ordering::Greater);
+    }
+
+    #[test]
+    fn order_scores_with_a_and_b_equal() {
+        assert_eq!(order_scores(0.3, 0.3), Ordering::Equal);
+    }
+
+    #[test]
+    fn order_scores_with_nan_is_a_total_order() {
+        assert_eq!(order_scores(f32::NAN, f32::NAN), Ordering::Equal);
+        assert_eq!(order_scores(f32::NAN, 0.3), Ordering::Less);
+        assert_eq!(order_scores(0.3, f32::NAN), Ordering::Greater);
+    }
+
+    #[test]
+    fn ranking_with_nan_scores_ranks_every_path() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (0.5, "a.ts".into()),
+            (f32::NAN, "b.ts".into()),
+            (0.9, "c.ts".into()),
+            (f32::NAN, "d.ts".into()),
+        ];
+
+        for limit in [0, 3] {
+            let paths: Vec<PathBuf> = rank(limit, &[], &scored_paths)
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect();
+            let expected = ["b.ts", "d.ts", "c.ts", "a.ts"].map(PathBuf::from);
+            assert_eq!(paths, expected[..paths.len()]);
+        }
+    }
+
+    #[test]
+    fn ranking_with_zero_limit_ranks_every_path() {
+        let ranked = rank(0, &[], &scored_paths());
+
+        let scores: Vec<f32> = ranked.iter().map(|s| s.0).collect();
+        assert_eq!(scores, vec![0.8, 0.4, 0.2, 0.1, 0.023]);
+    }
+
+    #[test]
+    fn ranking_with_non_zero_limit_keeps_the_top_paths() {
+        let ranked = rank(3, &[], &scored_paths());
+
+        let scores: Vec<f32> = ranked.iter().map(|s| s.0).collect();
+        assert_eq!(scores, vec![0.8, 0.4, 0.2]);
+    }
+
+    #[test]
+    fn ranking_ranks_ties_in_input_order() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (0.5, "a.ts".into()),
+            (0.9, "b.ts".into()),
+            (0.5, "c.ts".into()),
+            (0.5, "d.ts".into()),
+        ];
+
+        let ranked = rank(3, &[], &scored_paths);
+        assert_eq!(
+            ranked,
+            scored_paths_of(&[(0.9, "b.ts"), (0.5, "a.ts"), (0.5, "c.ts")])
+        );
+    }
+
+    #[test]
+    fn ranking_ranks_ties_at_the_same_position_by_path() {
+        let mut ranking = Ranking::new(2, vec![]);
+        ranking.push(0, (0.5, "c.ts".into()));
+        ranking.push(0, (0.5, "a/z.ts".into()));
+        ranking.push(0, (0.5, "a.ts".into()));
+
+        assert_eq!(
+            ranking.into_scored_paths(),
+            scored_paths_of(&[(0.5, "a/z.ts"), (0.5, "a.ts")])
+        );
+    }
+
+    #[test]
+    fn ranking_moves_projected_paths_to_the_front_in_order() {
+        let scored_paths: Vec<ScoredPath> = vec![
+            (0.8, "a.ts".into()),
+            (0.4, "b.ts".into()),
+            (0.2, "c.ts".into()),
+        ];
+
+        let ranked = rank(0, &["c.ts", "missing.ts", "b.ts"], &scored_paths);
+        assert_eq!(
+            ranked,
+            scored_paths_of(&[(0.2, "c.ts"), (0.4, "b.ts"), (0.8, "a.ts")])
+        );
+    }
+
+    #[test]
+    fn ranking_keeps_projected_paths_that_would_not_make_the_top() {
+        let ranked = rank(2, &["some/blue/fortytwo/sports_ball.ts"], &scored_paths());
+
+        assert_eq!(
+            ranked,
+            scored_paths_of(&[
+                (0.023, "some/blue/fortytwo/sports_ball.ts"),
+                (0.8, "some/path/to/a/file.ts"),
+            ])
+        );
+    }
+
+    #[test]
+    fn ranking_merged_from_parts_matches_ranking_everything_at_once() {
+        let scored_paths = scored_paths();
+        let projections = vec![PathBuf::from("some/short/path/zoo.ts")];
+
+        for limit in 0..scored_paths.len() + 1 {
+            let mut first = Ranking::new(limit, projections.clone());
+            let mut second = Ranking::new(limit, projections.clone());
+            for (index, scored_path) in scored_paths.iter().enumerate() {
+                if index % 2 == 0 {
+                    first.push(index, scored_path.clone());
+                } else {
+                    second.push(index, scored_path.clone());
+                }
+            }
+            second.merge(first);
+
+            assert_eq!(
+                second.into_scored_paths(),
+                rank(limit, &["some/short/path/zoo.ts"], &scored_paths)
+            );
+        }
+    }
+
+    fn scored_paths_of(scored_paths: &[(f32, &str)]) -> Vec<ScoredPath> {
+        scored_paths
+            .iter()
+            .map(|(score, path)| (*score, PathBuf::from(path)))
+            .collect()
+    }
+}
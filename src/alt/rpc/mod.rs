@@ -0,0 +1,415 @@
+use alt::config::PartialConfig;
+use alt::output::alternates_to_json;
+use alt::path::scoring::explain::explain;
+use alt::path::utils::{cleanse_path, SerializedPath};
+use alt::rules::projecting_rule;
+use alt::session::Session;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use serde_json::Value;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+// The error codes defined by JSON-RPC 2.0, along with one in the range left
+// for servers to define.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request was valid but could not be answered, e.g. because a config
+/// file is invalid.
+pub const REQUEST_FAILED: i64 = -32000;
+
+/// A JSON-RPC 2.0 request, or a notification when it has no id.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// A JSON-RPC 2.0 response, with either the result of the request or why it
+/// failed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Response {
+    jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Result(Value),
+    Error(ResponseError),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl ResponseError {
+    pub fn new(code: i64, message: String) -> Self {
+        ResponseError { code, message }
+    }
+}
+
+impl Response {
+    pub fn new(id: Value, result: Result<Value, ResponseError>) -> Self {
+        Response {
+            jsonrpc: "2.0",
+            id,
+            outcome: match result {
+                Ok(result) => Outcome::Result(result),
+                Err(error) => Outcome::Error(error),
+            },
+        }
+    }
+}
+
+/// Read the body of the next message, framed by headers like the Language
+/// Server Protocol's, or none when the input ended between messages.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut content_length: Option<usize> = None;
+    let mut read_header = false;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return match read_header {
+                false => Ok(None),
+                true => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            if read_header {
+                break;
+            }
+            // tolerate blank lines between messages
+            continue;
+        }
+        read_header = true;
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("Invalid header {}", header)))?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            content_length = Some(
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid Content-Length {}", value.trim())))?,
+            );
+        }
+    }
+
+    let content_length =
+        content_length.ok_or_else(|| invalid("Missing Content-Length header".to_string()))?;
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Write the message as JSON, framed by its Content-Length header.
+pub fn write_message<W: Write, T: Serialize>(writer: &mut W, message: &T) -> io::Result<()> {
+    let body = serde_json::to_vec(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n", body.len())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+/// Read messages until the next request or notification, answering the
+/// ones that are neither with an error and skipping responses to requests
+/// sent to the client. None when the input ended.
+pub fn read_request<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+) -> io::Result<Option<Request>> {
+    while let Some(body) = read_message(reader)? {
+        let value: Value = match serde_json::from_slice(&body) {
+            Ok(value) => value,
+            Err(e) => {
+                let error = ResponseError::new(PARSE_ERROR, format!("Invalid JSON, {}", e));
+                write_message(writer, &Response::new(Value::Null, Err(error)))?;
+                continue;
+            }
+        };
+        let is_response = value.get("method").is_none()
+            && (value.get("result").is_some() || value.get("error").is_some());
+        if is_response {
+            continue;
+        }
+        match serde_json::from_value(value.clone()) {
+            Ok(request) => return Ok(Some(request)),
+            Err(e) => {
+                let id = value.get("id").cloned().unwrap_or(Value::Null);
+                let error = ResponseError::new(INVALID_REQUEST, format!("Invalid request, {}", e));
+                write_message(writer, &Response::new(id, Err(error)))?;
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Answer the requests read from `reader` until it ends, writing the
+/// responses to `writer`. The session keeps the configuration and the
+/// indexes of the possible alternates between requests.
+pub fn serve<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    session: &mut Session,
+) -> io::Result<()> {
+    while let Some(request) = read_request(reader, writer)? {
+        let result = handle_request(session, &request.method, request.params);
+        // notifications are handled all the same, but not answered
+        if let Some(id) = request.id {
+            write_message(writer, &Response::new(id, result))?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AlternatesParams {
+    path: SerializedPath,
+    #[serde(default)]
+    options: PartialConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExplainParams {
+    path: SerializedPath,
+    #[serde(default)]
+    candidate: Option<SerializedPath>,
+    #[serde(default)]
+    options: PartialConfig,
+}
+
+#[derive(Serialize)]
+struct JsonExplanation {
+    path: SerializedPath,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    projected_by: Option<JsonRule>,
+    total: f32,
+    explanation: String,
+}
+
+#[derive(Serialize)]
+struct JsonRule {
+    pattern: String,
+    template: String,
+}
+
+fn handle_request(
+    session: &mut Session,
+    method: &str,
+    params: Value,
+) -> Result<Value, ResponseError> {
+    let failed = |e: &dyn ToString| ResponseError::new(REQUEST_FAILED, e.to_string());
+
+    match method {
+        "alternates" => {
+            let params: AlternatesParams = parse_params(params)?;
+            let cleansed_path = cleanse_path(&params.path.to_path_buf());
+            let config = session.config(params.options);
+            let scored_paths = session
+                .alternates(&config, &cleansed_path)
+                .map_err(|e| failed(&e))?;
+            Ok(alternates_to_json(
+                &cleansed_path,
+                &config.scorer(),
+                &scored_paths,
+            ))
+        }
+        "explain" => {
+            let params: ExplainParams = parse_params(params)?;
+            let cleansed_path = cleanse_path(&params.path.to_path_buf());
+            let config = session.config(params.options);
+            let rules = config.compile_rules().map_err(|e| failed(&e))?;
+            let scorer = config.scorer();
+            let candidate_paths: Vec<PathBuf> = match params.candidate {
+                Some(candidate) => vec![cleanse_path(&candidate.to_path_buf())],
+                None => session
+                    .alternates(&config, &cleansed_path)
+                    .map_err(|e| failed(&e))?
+                    .into_iter()
+                    .map(|(_, path)| path)
+                    .collect(),
+            };
+            let explanations: Vec<JsonExplanation> = candidate_paths
+                .iter()
+                .map(|candidate_path| {
+                    let explanation = explain(&scorer, &cleansed_path, candidate_path);
+                    JsonExplanation {
+                        path: SerializedPath::new(candidate_path),
+                        projected_by: cleansed_path
+                            .to_str()
+                            .and_then(|path| projecting_rule(&rules, path, candidate_path))
+                            .map(|rule| JsonRule {
+                                pattern: rule.pattern().to_string(),
+                                template: rule.template().to_string(),
+                            }),
+                        total: explanation.total(),
+                        explanation: explanation.to_string(),
+                    }
+                })
+                .collect();
+            Ok(serde_json::to_value(explanations).expect("explanations are always valid JSON"))
+        }
+        "reload" => {
+            session.reload().map_err(|e| failed(&e))?;
+            Ok(Value::Null)
+        }
+        _ => Err(ResponseError::new(
+            METHOD_NOT_FOUND,
+            format!(
+                "Unknown method {}, expected alternates, explain or reload",
+                method
+            ),
+        )),
+    }
+}
+
+/// Parse the params of a request, failing with the error JSON-RPC defines for
+/// invalid params.
+pub fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, ResponseError> {
+    serde_json::from_value(params)
+        .map_err(|e| ResponseError::new(INVALID_PARAMS, format!("Invalid params, {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::{
+        read_message, serve, write_message, INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR,
+        REQUEST_FAILED,
+    };
+    use alt::config::PartialConfig;
+    use alt::session::Session;
+    use serde_json;
+    use serde_json::Value;
+    use std::fs;
+    use std::io;
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    fn responses(output: &[u8]) -> Vec<Value> {
+        let mut reader = output;
+        let mut responses = vec![];
+        while let Some(body) = read_message(&mut reader).unwrap() {
+            responses.push(serde_json::from_slice(&body).unwrap());
+        }
+        responses
+    }
+
+    fn serve_requests(session: &mut Session, requests: &[&str]) -> Vec<Value> {
+        let input: String = requests.iter().map(|request| frame(request)).collect();
+        let mut output: Vec<u8> = vec![];
+        serve(&mut input.as_bytes(), &mut output, session).unwrap();
+        responses(&output)
+    }
+
+    #[test]
+    fn messages_round_trip_through_their_framing() {
+        let mut output: Vec<u8> = vec![];
+        write_message(&mut output, &serde_json::json!({"id": 1})).unwrap();
+        write_message(&mut output, &serde_json::json!({"id": "caf\u{e9}"})).unwrap();
+
+        assert_eq!(
+            responses(&output),
+            vec![
+                serde_json::json!({"id": 1}),
+                serde_json::json!({"id": "caf\u{e9}"})
+            ]
+        );
+    }
+
+    #[test]
+    fn read_message_ignores_other_headers_and_their_case() {
+        let input = "content-length: 2\r\nContent-Type: application/json\r\n\r\n{}";
+
+        assert_eq!(
+            read_message(&mut input.as_bytes()).unwrap(),
+            Some(b"{}".to_vec())
+        );
+    }
+
+    #[test]
+    fn read_message_fails_without_content_length() {
+        let error = read_message(&mut "Content-Type: json\r\n\r\n{}".as_bytes()).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_message_fails_when_the_input_ends_within_a_message() {
+        assert!(read_message(&mut "Content-Length: 10\r\n\r\n{}".as_bytes()).is_err());
+        assert!(read_message(&mut "Content-Length: 2\r\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn serve_explains_a_candidate_and_answers_errors_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session =
+            Session::with_user_config(dir.path(), None, PartialConfig::default()).unwrap();
+
+        let responses = serve_requests(
+            &mut session,
+            &[
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "explain", "params": {"path": "spec/models/user_spec.rb", "candidate": "app/models/user.rb", "options": {"rules": [{"pattern": "spec/**/*_spec.rb", "template": "app/{1}/{2}.rb"}]}}}"#,
+                r#"{"jsonrpc": "2.0", "id": 2, "method": "rename"}"#,
+                r#"{"jsonrpc": "2.0", "id": 3, "method": "alternates", "params": {"path": "a.rb", "options": {"colour": true}}}"#,
+                r#"{"jsonrpc": "2.0", "id": 4, "method": "#,
+            ],
+        );
+
+        assert_eq!(responses.len(), 4);
+        let explanation = &responses[0]["result"][0];
+        assert_eq!(explanation["path"], "app/models/user.rb");
+        assert_eq!(explanation["projected_by"]["template"], "app/{1}/{2}.rb");
+        assert!(explanation["explanation"]
+            .as_str()
+            .unwrap()
+            .contains("candidate: app/models/user.rb"));
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[2]["id"], 3);
+        assert_eq!(responses[2]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[3]["id"], Value::Null);
+        assert_eq!(responses[3]["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn serve_reloads_the_configuration_and_does_not_answer_notifications() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session =
+            Session::with_user_config(dir.path(), None, PartialConfig::default()).unwrap();
+        fs::write(dir.path().join(".alt.toml"), "truncate = \"one\"").unwrap();
+
+        let responses = serve_requests(
+            &mut session,
+            &[
+                r#"{"jsonrpc": "2.0", "method": "reload"}"#,
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "reload"}"#,
+            ],
+        );
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["error"]["code"], REQUEST_FAILED);
+    }
+}
@@ -0,0 +1,277 @@
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+/// A convention based rule that deterministically projects a path matching
+/// its pattern onto an alternate path built from its template.
+///
+/// Patterns are globs where each wildcard is a capture, numbered from left to
+/// right starting at 1. `*` matches within a single path segment and `**`
+/// matches any number of directories. Templates reference captures with
+/// `{1}`, `{2}`, etc. For example, the pattern `spec/**/*_spec.rb` with the
+/// template `app/{1}/{2}.rb` projects `spec/models/user_spec.rb` onto
+/// `app/models/user.rb`.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pattern: String,
+    template: String,
+    regex: Regex,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RuleError {
+    InvalidPattern(String),
+    UnknownCapture(String, usize),
+    InvalidTemplate(String),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::InvalidPattern(pattern) => write!(f, "Invalid rule pattern {}", pattern),
+            RuleError::UnknownCapture(template, index) => write!(
+                f,
+                "Rule template {} references capture {{{}}} which its pattern does not have",
+                template, index
+            ),
+            RuleError::InvalidTemplate(template) => {
+                write!(f, "Invalid rule template {}", template)
+            }
+        }
+    }
+}
+
+impl Rule {
+    pub fn new(pattern: &str, template: &str) -> Result<Rule, RuleError> {
+        let (regex_source, capture_count) = glob_to_regex(pattern);
+        let regex = Regex::new(&regex_source)
+            .map_err(|_| RuleError::InvalidPattern(pattern.to_string()))?;
+
+        for index in template_captures(template)? {
+            if index == 0 || index > capture_count {
+                return Err(RuleError::UnknownCapture(template.to_string(), index));
+            }
+        }
+
+        Ok(Rule {
+            pattern: pattern.to_string(),
+            template: template.to_string(),
+            regex,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Project the given path onto its alternate, if the path matches this
+    /// rule's pattern.
+    pub fn project(&self, path: &str) -> Option<String> {
+        let captures = self.regex.captures(path)?;
+
+        let mut projected = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}')?;
+            let index: usize = rest[start + 1..end].parse().ok()?;
+            projected.push_str(&rest[..start]);
+            projected.push_str(captures.get(index).map_or("", |m| m.as_str()));
+            rest = &rest[end + 1..];
+        }
+        projected.push_str(rest);
+
+        // captures of ** may be empty, so collapse the empty segments they leave
+        let segments: Vec<&str> = projected.split('/').filter(|s| !s.is_empty()).collect();
+        Some(segments.join("/"))
+    }
+}
+
+/// Project the given path with each of the rules, in order, returning the
+/// unique projected paths.
+pub fn project(rules: &[Rule], path: &str) -> Vec<String> {
+    let mut projections: Vec<String> = vec![];
+    for projection in rules.iter().filter_map(|rule| rule.project(path)) {
+        if projection != path && !projections.contains(&projection) {
+            projections.push(projection);
+        }
+    }
+    projections
+}
+
+/// The first of the rules projecting the given path onto the projection,
+/// which is the rule the projection was ranked by.
+pub fn projecting_rule<'r>(rules: &'r [Rule], path: &str, projection: &Path) -> Option<&'r Rule> {
+    rules.iter().find(|rule| {
+        rule.project(path)
+            .is_some_and(|projected| Path::new(&projected) == projection)
+    })
+}
+
+fn glob_to_regex(pattern: &str) -> (String, usize) {
+    let mut regex_source = String::from("^");
+    let mut capture_count = 0;
+    let mut rest = pattern;
+
+    while !rest.is_empty() {
+        if rest.starts_with("**/") {
+            regex_source.push_str("(?:(.*)/)?");
+            rest = &rest[3..];
+            capture_count += 1;
+        } else if rest.starts_with("**") {
+            regex_source.push_str("(.*)");
+            rest = &rest[2..];
+            capture_count += 1;
+        } else if rest.starts_with('*') {
+            regex_source.push_str("([^/]*)");
+            rest = &rest[1..];
+            capture_count += 1;
+        } else {
+            let c = rest.chars().next().unwrap();
+            regex_source.push_str(&regex::escape(&c.to_string()));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    regex_source.push('$');
+    (regex_source, capture_count)
+}
+
+fn template_captures(template: &str) -> Result<Vec<usize>, RuleError> {
+    let invalid = || RuleError::InvalidTemplate(template.to_string());
+    let mut indexes = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}').ok_or_else(invalid)?;
+        indexes.push(rest[start + 1..end].parse().map_err(|_| invalid())?);
+        rest = &rest[end + 1..];
+    }
+    Ok(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{project, projecting_rule, Rule, RuleError};
+    use std::path::Path;
+
+    #[test]
+    fn rule_projects_rails_spec_onto_implementation() {
+        let rule = Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap();
+        assert_eq!(
+            rule.project("spec/queues/fee/user_fee_submission_spec.rb"),
+            Some("app/queues/fee/user_fee_submission.rb".to_string())
+        );
+    }
+
+    #[test]
+    fn rule_projects_when_double_star_matches_no_directories() {
+        let rule = Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap();
+        assert_eq!(
+            rule.project("spec/user_spec.rb"),
+            Some("app/user.rb".to_string())
+        );
+    }
+
+    #[test]
+    fn rule_projects_go_test_onto_implementation() {
+        let rule = Rule::new("**/*_test.go", "{1}/{2}.go").unwrap();
+        assert_eq!(
+            rule.project("pkg/server/handler_test.go"),
+            Some("pkg/server/handler.go".to_string())
+        );
+        assert_eq!(
+            rule.project("handler_test.go"),
+            Some("handler.go".to_string())
+        );
+    }
+
+    #[test]
+    fn rule_projects_jest_test_onto_implementation() {
+        let rule = Rule::new("**/__tests__/*.test.ts", "{1}/{2}.ts").unwrap();
+        assert_eq!(
+            rule.project("src/components/__tests__/button.test.ts"),
+            Some("src/components/button.ts".to_string())
+        );
+    }
+
+    #[test]
+    fn rule_does_not_project_paths_that_do_not_match() {
+        let rule = Rule::new("tests/test_*.py", "{1}.py").unwrap();
+        assert_eq!(rule.project("src/test_foo.py"), None);
+        assert_eq!(rule.project("tests/nested/test_foo.py"), None);
+    }
+
+    #[test]
+    fn rule_escapes_regex_characters_in_patterns() {
+        let rule = Rule::new("lib/*.(rb)", "spec/{1}_spec.rb").unwrap();
+        assert_eq!(
+            rule.project("lib/foo.(rb)"),
+            Some("spec/foo_spec.rb".to_string())
+        );
+        assert_eq!(rule.project("lib/foo.rb"), None);
+    }
+
+    #[test]
+    fn rule_new_rejects_templates_with_unknown_captures() {
+        assert_eq!(
+            Rule::new("spec/*_spec.rb", "app/{2}.rb").unwrap_err(),
+            RuleError::UnknownCapture("app/{2}.rb".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn rule_new_rejects_malformed_templates() {
+        assert_eq!(
+            Rule::new("spec/*_spec.rb", "app/{1.rb").unwrap_err(),
+            RuleError::InvalidTemplate("app/{1.rb".to_string())
+        );
+        assert_eq!(
+            Rule::new("spec/*_spec.rb", "app/{name}.rb").unwrap_err(),
+            RuleError::InvalidTemplate("app/{name}.rb".to_string())
+        );
+    }
+
+    #[test]
+    fn project_returns_unique_projections_in_rule_order() {
+        let rules = vec![
+            Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap(),
+            Rule::new("spec/**/*_spec.rb", "lib/{1}/{2}.rb").unwrap(),
+            Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap(),
+        ];
+        assert_eq!(
+            project(&rules, "spec/models/user_spec.rb"),
+            vec!["app/models/user.rb", "lib/models/user.rb"]
+        );
+    }
+
+    #[test]
+    fn project_with_no_matching_rules_is_empty() {
+        let rules = vec![Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap()];
+        assert!(project(&rules, "app/models/user.rb").is_empty());
+    }
+
+    #[test]
+    fn projecting_rule_is_the_first_rule_projecting_onto_the_path() {
+        let rules = vec![
+            Rule::new("spec/**/*_spec.rb", "app/{1}/{2}.rb").unwrap(),
+            Rule::new("spec/**/*_spec.rb", "lib/{1}/{2}.rb").unwrap(),
+        ];
+        assert_eq!(
+            projecting_rule(
+                &rules,
+                "spec/models/user_spec.rb",
+                Path::new("lib/models/user.rb")
+            )
+            .map(Rule::template),
+            Some("lib/{1}/{2}.rb")
+        );
+        assert_eq!(
+            projecting_rule(&rules, "spec/models/user_spec.rb", Path::new("app/user.rb"))
+                .map(Rule::template),
+            None
+        );
+    }
+}
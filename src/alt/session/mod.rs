@@ -0,0 +1,301 @@
+use alt::config::{user_config_path, CandidateSource, Config, ConfigError, PartialConfig, Source};
+use alt::git::{ls_files, GitError};
+use alt::index::index_path;
+use alt::index::watched::WatchedIndex;
+use alt::path::kind::classify;
+use alt::path::scoring::{Candidate, ScoredPath};
+use alt::rules::Rule;
+use alt::{find_alt, find_alt_with_threads, thread_pool, walk_builder, FindAltWithThreadsError};
+use rayon::ThreadPool;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The state a long running alt, e.g. one serving an editor, keeps between
+/// requests: the configuration of its directory, the watched indexes of the
+/// possible alternates below it and the pool of threads ranking them.
+pub struct Session {
+    cwd: PathBuf,
+    user_config: Option<PathBuf>,
+    overrides: PartialConfig,
+    config: Config,
+    // requests may or may not include hidden entries, each needing an index
+    // of its own
+    indexes: HashMap<bool, WatchedIndex>,
+    // the pool along with the number of threads it was built for, rebuilt
+    // only when a request asks for another number
+    pool: Option<(usize, ThreadPool)>,
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Config(ConfigError),
+    Threads(FindAltWithThreadsError),
+    Git(GitError),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Config(e) => write!(f, "{}", e),
+            SessionError::Threads(e) => write!(f, "{}", e),
+            SessionError::Git(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<ConfigError> for SessionError {
+    fn from(e: ConfigError) -> Self {
+        SessionError::Config(e)
+    }
+}
+
+impl From<FindAltWithThreadsError> for SessionError {
+    fn from(e: FindAltWithThreadsError) -> Self {
+        SessionError::Threads(e)
+    }
+}
+
+impl From<GitError> for SessionError {
+    fn from(e: GitError) -> Self {
+        SessionError::Git(e)
+    }
+}
+
+impl Session {
+    /// Start a session for the working directory, loading its configuration
+    /// with the given overrides, e.g. the options given on the command line.
+    pub fn new(cwd: &Path, overrides: PartialConfig) -> Result<Self, ConfigError> {
+        Session::with_user_config(cwd, user_config_path().as_deref(), overrides)
+    }
+
+    /// Start a session like `new` does, but with the user config read from
+    /// `user_config`, if any, rather than from where it lives for the user.
+    pub fn with_user_config(
+        cwd: &Path,
+        user_config: Option<&Path>,
+        overrides: PartialConfig,
+    ) -> Result<Self, ConfigError> {
+        Ok(Session {
+            cwd: cwd.to_path_buf(),
+            user_config: user_config.map(Path::to_path_buf),
+            config: Config::load_from(cwd, user_config, overrides.clone())?,
+            overrides,
+            indexes: HashMap::new(),
+            pool: None,
+        })
+    }
+
+    /// Load the configuration again, e.g. after the config files changed.
+    /// The configuration is left as it was when loading fails.
+    pub fn reload(&mut self) -> Result<(), ConfigError> {
+        self.config = Config::load_from(
+            &self.cwd,
+            self.user_config.as_deref(),
+            self.overrides.clone(),
+        )?;
+        Ok(())
+    }
+
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// The configuration of the session with the overrides of a request
+    /// layered on top.
+    pub fn config(&self, overrides: PartialConfig) -> Config {
+        let mut config = self.config.clone();
+        config.merge(overrides, Source::CommandLine);
+        config
+    }
+
+    /// Rank the possible alternates below the working directory for the
+    /// cleansed path with the given configuration. Those listed by git are
+    /// listed again for every request, as git keeps them up to date itself.
+    pub fn alternates(
+        &mut self,
+        config: &Config,
+        cleansed_path: &Path,
+    ) -> Result<Vec<ScoredPath>, SessionError> {
+        let rules = config.compile_rules()?;
+        let keep = keep_filter(&self.cwd, config, cleansed_path)?;
+        let pool = reusable_pool(&mut self.pool, config.threads.value)?;
+        let untracked = match config.source.value {
+            CandidateSource::Walk => None,
+            CandidateSource::Git => Some(false),
+            CandidateSource::GitUntracked => Some(true),
+        };
+        if let Some(untracked) = untracked {
+            let paths = ls_files(&self.cwd, untracked, config.include_hidden.value)?;
+            let paths = paths.into_iter().filter(|path| keep(path));
+            return Ok(rank_on(pool, cleansed_path, paths, config, &rules)?);
+        }
+        let include_hidden = config.include_hidden.value;
+        let index_path = match config.cache.value {
+            true => index_path(&self.cwd, include_hidden),
+            false => None,
+        };
+        let threads = config.threads.value;
+        let index = self.indexes.entry(include_hidden).or_insert_with(|| {
+            WatchedIndex::new(
+                Path::new("./"),
+                index_path.as_deref(),
+                threads,
+                move |root: &Path| walk_builder(root, !include_hidden),
+            )
+        });
+        let paths = index.indexed_paths().iter().filter(|p| keep(&p.path));
+        Ok(rank_on(pool, cleansed_path, paths, config, &rules)?)
+    }
+}
+
+// The pool to rank on with the given number of threads, none for a single
+// thread, reusing the last one built when it has as many.
+fn reusable_pool(
+    pool: &mut Option<(usize, ThreadPool)>,
+    threads: usize,
+) -> Result<Option<&ThreadPool>, FindAltWithThreadsError> {
+    if threads == 1 {
+        return Ok(None);
+    }
+    if !matches!(pool, Some((built_for, _)) if *built_for == threads) {
+        *pool = Some((threads, thread_pool(threads)?));
+    }
+    Ok(pool.as_ref().map(|(_, pool)| pool))
+}
+
+/// Whether to keep a possible alternate, rather than it being ignored by the
+/// configured patterns or not being of the kind asked for.
+pub fn keep_filter(
+    cwd: &Path,
+    config: &Config,
+    cleansed_path: &Path,
+) -> Result<impl Fn(&Path) -> bool + Sync, ConfigError> {
+    let ignore_matcher = config.ignore_matcher(cwd)?;
+    let opposite_kind = match config.opposite.value {
+        true => Some(classify(cleansed_path).opposite()),
+        false => None,
+    };
+    Ok(move |path: &Path| {
+        !ignore_matcher.is_ignored(path) && opposite_kind.is_none_or(|kind| classify(path) == kind)
+    })
+}
+
+/// Rank the possible alternates on the calling thread, or on a pool of the
+/// configured number of threads.
+pub fn rank<C, I>(
+    cleansed_path: &Path,
+    paths: I,
+    config: &Config,
+    rules: &[Rule],
+) -> Result<Vec<ScoredPath>, FindAltWithThreadsError>
+where
+    C: Candidate + Send,
+    I: IntoIterator<Item = C>,
+{
+    let pool = match config.threads.value {
+        1 => None,
+        threads => Some(thread_pool(threads)?),
+    };
+    rank_on(pool.as_ref(), cleansed_path, paths, config, rules)
+}
+
+/// Rank the possible alternates on the calling thread, or on the given pool,
+/// e.g. one reused to rank the alternates of many paths.
+pub fn rank_on<C, I>(
+    pool: Option<&ThreadPool>,
+    cleansed_path: &Path,
+    paths: I,
+    config: &Config,
+    rules: &[Rule],
+) -> Result<Vec<ScoredPath>, FindAltWithThreadsError>
+where
+    C: Candidate + Send,
+    I: IntoIterator<Item = C>,
+{
+    let scorer = config.scorer();
+    match pool {
+        None => Ok(find_alt(
+            cleansed_path,
+            paths,
+            config.truncate.value,
+            rules,
+            &scorer,
+        )),
+        Some(pool) => find_alt_with_threads(
+            cleansed_path,
+            paths,
+            config.truncate.value,
+            rules,
+            &scorer,
+            pool,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keep_filter, reusable_pool};
+    use alt::config::{Config, PartialConfig, Source};
+    use rayon::ThreadPool;
+    use std::path::Path;
+    use std::thread;
+    use std::thread::ThreadId;
+
+    fn opposite_config() -> Config {
+        let mut config = Config::default();
+        config.merge(
+            PartialConfig {
+                opposite: Some(true),
+                ..PartialConfig::default()
+            },
+            Source::CommandLine,
+        );
+        config
+    }
+
+    #[test]
+    fn keep_filter_keeps_only_implementations_for_a_test() {
+        let keep = keep_filter(
+            Path::new("/repo"),
+            &opposite_config(),
+            Path::new("spec/queues/fee/user_fee_submission_spec.rb"),
+        )
+        .unwrap();
+
+        assert!(!keep(Path::new("spec/queues/fee/user_fee_spec.rb")));
+        assert!(keep(Path::new("app/queues/fee/user_fee_submission.rb")));
+        assert!(!keep(Path::new("spec/fixtures/user_fee_submission.yml")));
+    }
+
+    #[test]
+    fn keep_filter_keeps_only_tests_for_an_implementation() {
+        let keep = keep_filter(
+            Path::new("/repo"),
+            &opposite_config(),
+            Path::new("app/queues/fee/user_fee_submission.rb"),
+        )
+        .unwrap();
+
+        assert!(keep(Path::new(
+            "spec/queues/fee/user_fee_submission_spec.rb"
+        )));
+        assert!(!keep(Path::new("app/queues/fee/user_fee.rb")));
+    }
+
+    #[test]
+    fn reusable_pool_is_rebuilt_only_for_another_number_of_threads() {
+        let mut pool = None;
+        // the threads of the pool, which a rebuilt pool does not share
+        let threads = |pool: Option<&ThreadPool>| -> Vec<ThreadId> {
+            pool.unwrap().broadcast(|_| thread::current().id())
+        };
+
+        assert!(reusable_pool(&mut pool, 1).unwrap().is_none());
+        let built = threads(reusable_pool(&mut pool, 2).unwrap());
+        assert_eq!(threads(reusable_pool(&mut pool, 2).unwrap()), built);
+        let rebuilt = threads(reusable_pool(&mut pool, 3).unwrap());
+        assert_eq!(rebuilt.len(), 3);
+        assert!(rebuilt.iter().all(|thread| !built.contains(thread)));
+    }
+}
@@ -1,16 +1,42 @@
 extern crate argparse;
+extern crate crossterm;
 extern crate ignore;
+extern crate notify;
+extern crate rayon;
+extern crate regex;
+#[macro_use]
+extern crate serde;
+extern crate serde_json;
+extern crate toml;
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
 
+use alt::config::{config_to_string, CandidateSource, Config, PartialConfig, Source};
+#[cfg(unix)]
+use alt::daemon::{bind, request, serve, socket_path, Request, Response};
+use alt::git::{diff_files, ls_files, Changes};
+use alt::index::{index_path, load_index, Index, IndexedPath};
+use alt::interactive::{pick, Choice};
+use alt::lsp;
+use alt::output::{scored_paths_to_bytes, write_alternates, write_batch, Format};
+use alt::path::scoring::explain::explain;
 use alt::path::scoring::ScoredPath;
-use alt::path::utils::cleanse_path;
-use alt::{find_alt, find_alt_with_threads};
-use argparse::{ArgumentParser, Print, Store, StoreOption, StoreTrue};
-use ignore::WalkBuilder;
+use alt::path::utils::{cleanse_path, path_from_bytes, SerializedPath};
+use alt::rpc;
+use alt::rules::{projecting_rule, Rule};
+use alt::session::{keep_filter, rank, rank_on, Session};
+use alt::{find_alt_with_parallel_walk, thread_pool, walk_builder, FindAltWithThreadsError};
+use argparse::{ArgumentParser, Collect, Print, Store, StoreConst, StoreOption, StoreTrue};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 pub mod alt;
 
@@ -22,43 +48,177 @@ macro_rules! printerr(
 );
 
 struct Options {
-    path: String,
-    possible_alternates_path: Option<String>,
-    include_hidden: bool,
-    truncate: usize,
-    leading_edge_filename_weight: f32,
-    filename_weight: f32,
-    path_weight: f32,
-    use_threads: bool,
-}
-
-fn get_possible_files(ignore_hidden: bool) -> Vec<PathBuf> {
-    WalkBuilder::new("./")
-        .follow_links(true)
-        .hidden(ignore_hidden)
+    path: PathBuf,
+    possible_alternates_path: Option<PathBuf>,
+    delimiter: u8,
+    format: Format,
+    interactive: bool,
+    config: ConfigOptions,
+}
+
+struct BatchOptions {
+    paths: Vec<PathBuf>,
+    possible_alternates_path: Option<PathBuf>,
+    delimiter: u8,
+    format: Format,
+    config: ConfigOptions,
+}
+
+struct ChangedOptions {
+    changes: Changes,
+    possible_alternates_path: Option<PathBuf>,
+    delimiter: u8,
+    config: ConfigOptions,
+}
+
+struct ExplainOptions {
+    path: PathBuf,
+    candidate_path: Option<PathBuf>,
+    possible_alternates_path: Option<PathBuf>,
+    delimiter: u8,
+    config: ConfigOptions,
+}
+
+#[derive(Default)]
+struct ConfigOptions {
+    overrides: PartialConfig,
+    profiles: Vec<String>,
+}
+
+impl ConfigOptions {
+    fn into_partial_config(self) -> PartialConfig {
+        let mut overrides = self.overrides;
+        if !self.profiles.is_empty() {
+            // none lets you opt out of profiles, including detected ones
+            overrides.profiles = Some(
+                self.profiles
+                    .into_iter()
+                    .filter(|profile| profile != "none")
+                    .collect(),
+            );
+        }
+        overrides
+    }
+}
+
+// The walk is sorted by file name so that paths with equal scores are ranked
+// in the same order as a parallel walk ranks them, by path.
+fn get_possible_files(ignore_hidden: bool) -> impl Iterator<Item = PathBuf> {
+    walk_builder(Path::new("./"), ignore_hidden)
+        .sort_by_file_name(|a, b| a.cmp(b))
         .build()
         .filter_map(|direntry| {
             let entry = direntry.ok()?;
             if entry.file_type()?.is_file() {
-                Some(entry.path().to_owned())
+                Some(entry.into_path())
             } else {
                 None
             }
         })
-        .collect()
 }
 
-fn parse_args_or_exit() -> Options {
-    let mut options = Options {
-        path: "".to_string(),
-        possible_alternates_path: None,
-        include_hidden: false,
-        truncate: 0,
-        leading_edge_filename_weight: 100.0,
-        filename_weight: 10.0,
-        path_weight: 1.0,
-        use_threads: false,
-    };
+fn add_config_options<'parser>(
+    ap: &mut ArgumentParser<'parser>,
+    config: &'parser mut ConfigOptions,
+) {
+    let overrides = &mut config.overrides;
+    ap.refer(&mut overrides.truncate).add_option(
+        &["-t", "--truncate"],
+        StoreOption,
+        "truncate results to provided length. 0 = don't truncate, > 0 = truncate",
+    );
+    ap.refer(&mut overrides.weights.leading_edge_filename).add_option(
+        &["--leading-edge-filename-weight"],
+        StoreOption,
+        "override the default weight of leading edge filenames in the scoring algorithm (default: 100.0)",
+    );
+    ap.refer(&mut overrides.weights.filename).add_option(
+        &["--filename-weight"],
+        StoreOption,
+        "override the default weight of filenames in the scoring algorithm (default: 10.0)",
+    );
+    ap.refer(&mut overrides.weights.path).add_option(
+        &["--path-weight"],
+        StoreOption,
+        "override the default weight of paths in the scoring algorithm (default: 1.0)",
+    );
+    ap.refer(&mut overrides.weights.token_filename).add_option(
+        &["--token-filename-weight"],
+        StoreOption,
+        "override the default weight of filename tokens matched in order in the scoring algorithm (default: 0.0)",
+    );
+    ap.refer(&mut overrides.weights.unordered_token_filename).add_option(
+        &["--unordered-token-filename-weight"],
+        StoreOption,
+        "override the default weight of filename tokens matched in any order in the scoring algorithm (default: 0.0)",
+    );
+    ap.refer(&mut overrides.include_hidden).add_option(
+        &["-a"],
+        StoreConst(Some(true)),
+        "include directory entries whose names begin with a dot",
+    );
+    ap.refer(&mut overrides.threads)
+        .add_option(
+            &["-j"],
+            StoreConst(Some(0)),
+            "walk directories & do similarity scoring with one thread per CPU, same as --threads 0",
+        )
+        .add_option(
+            &["--threads"],
+            StoreOption,
+            "number of threads to walk directories & do similarity scoring with. 0 = one per CPU (default: 1)",
+        );
+    ap.refer(&mut overrides.opposite).add_option(
+        &["--opposite"],
+        StoreConst(Some(true)),
+        "only output alternates of the opposite kind, e.g. implementations for a test and tests for an implementation",
+    );
+    ap.refer(&mut overrides.graphemes).add_option(
+        &["--graphemes"],
+        StoreConst(Some(true)),
+        "compare filenames and paths by grapheme clusters rather than Unicode scalar values (default: false)",
+    );
+    ap.refer(&mut overrides.cache).add_option(
+        &["--no-cache"],
+        StoreConst(Some(false)),
+        "walk the directories rather than using the cached index of possible alternates or a running daemon",
+    );
+    ap.refer(&mut overrides.source).add_option(
+        &["--source"],
+        StoreOption,
+        "where the possible alternates come from, walk - walking the directories, honoring ignore files, git - the files git tracks, git-untracked - the files git tracks and the untracked files it does not ignore (default: walk)",
+    );
+    ap.refer(&mut config.profiles).add_option(
+        &["--profile"],
+        Collect,
+        "use the conventions of a built-in profile, may be repeated, none = no profiles (default: detected from marker files)",
+    );
+}
+
+fn add_null_option<'parser>(ap: &mut ArgumentParser<'parser>, null: &'parser mut bool) {
+    ap.refer(null).add_option(
+        &["-0", "--null"],
+        StoreTrue,
+        "separate the paths read from the possible alternates file, and the alternates output, by NUL rather than newline",
+    );
+}
+
+// Paths are separated by newlines, or by NUL so that they may contain
+// newlines.
+fn delimiter(null: bool) -> u8 {
+    match null {
+        true => b'\0',
+        false => b'\n',
+    }
+}
+
+fn parse_args_or_exit(args_os: &[OsString]) -> Options {
+    let mut path = String::new();
+    let mut possible_alternates_path: Option<String> = None;
+    let mut null = false;
+    let mut format = Format::default();
+    let mut interactive = false;
+    let mut config = ConfigOptions::default();
 
     {
         // block limits of borrows by refer() method calls
@@ -68,177 +228,1129 @@ fn parse_args_or_exit() -> Options {
             Print(env!("CARGO_PKG_VERSION").to_string()),
             "show version",
         );
-        ap.refer(&mut options.truncate).add_option(
-            &["-t", "--truncate"],
-            Store,
-            "truncate results to provided length. 0 = don't truncate, > 0 = truncate",
+        add_config_options(&mut ap, &mut config);
+        ap.refer(&mut possible_alternates_path).add_option(
+            &["-f", "--file"],
+            StoreOption,
+            "possible alternates file, - for stdin",
         );
-        ap.refer(&mut options.leading_edge_filename_weight).add_option(
-            &["--leading-edge-filename-weight"],
-            Store,
-            "override the default weight of leading edge filenames in the scoring algorithm (default: 100.0)",
+        add_null_option(&mut ap, &mut null);
+        ap.refer(&mut format)
+            .add_option(
+                &["--format"],
+                Store,
+                "output format, plain, json, jsonl, fzf, fzf-preview, quickfix, emacs or vscode, the json formats include scores and their components, fzf outputs score<TAB>path and fzf-preview adds the kind and score components, the editor formats output locations for Vim's quickfix list, Emacs' compilation mode and VS Code's $msCompile problem matcher (default: plain)",
+            )
+            .add_option(&["--fzf"], StoreConst(Format::Fzf), "same as --format fzf");
+        ap.refer(&mut interactive).add_option(
+            &["-i", "--interactive"],
+            StoreTrue,
+            "choose among the ranked alternates in the terminal, narrowing them by typing, then output the chosen one",
         );
-        ap.refer(&mut options.filename_weight).add_option(
-            &["--filename-weight"],
-            Store,
-            "override the default weight of filenames in the scoring algorithm (default: 10.0)",
+        ap.refer(&mut path)
+            .add_argument("PATH", Store, "path to find alternate for")
+            .required();
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(lossy_args(args_os), &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
+    }
+
+    Options {
+        path: original_arg(args_os, &path),
+        possible_alternates_path: possible_alternates_path.map(|p| original_arg(args_os, &p)),
+        delimiter: delimiter(null),
+        format,
+        interactive,
+        config,
+    }
+}
+
+fn lossy_args(args_os: &[OsString]) -> Vec<String> {
+    args_os
+        .iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+// Arguments are parsed as lossily converted strings, so find the original of
+// a parsed argument to preserve paths that are not valid UTF-8.
+fn original_arg(args_os: &[OsString], arg: &str) -> PathBuf {
+    args_os
+        .iter()
+        .rev()
+        .find(|arg_os| arg_os.to_string_lossy() == arg)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(arg))
+}
+
+fn parse_explain_args_or_exit(args_os: &[OsString]) -> ExplainOptions {
+    let mut path = String::new();
+    let mut candidate_path: Option<String> = None;
+    let mut possible_alternates_path: Option<String> = None;
+    let mut null = false;
+    let mut config = ConfigOptions::default();
+
+    // present `explain` as part of the program name in usage and errors
+    let mut args: Vec<String> = vec![format!("{} explain", args_os[0].to_string_lossy())];
+    args.extend(lossy_args(&args_os[2..]));
+
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Explain how the candidate, or each ranked alternate, was scored for PATH",
         );
-        ap.refer(&mut options.path_weight).add_option(
-            &["--path-weight"],
-            Store,
-            "override the default weight of paths in the scoring algorithm (default: 1.0)",
+        add_config_options(&mut ap, &mut config);
+        ap.refer(&mut possible_alternates_path).add_option(
+            &["-f", "--file"],
+            StoreOption,
+            "possible alternates file, - for stdin",
         );
-        ap.refer(&mut options.possible_alternates_path).add_option(
+        add_null_option(&mut ap, &mut null);
+        ap.refer(&mut path)
+            .add_argument("PATH", Store, "path to find alternate for")
+            .required();
+        ap.refer(&mut candidate_path).add_argument(
+            "CANDIDATE",
+            StoreOption,
+            "candidate to explain (default: each ranked alternate)",
+        );
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(args, &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
+    }
+
+    ExplainOptions {
+        path: original_arg(args_os, &path),
+        candidate_path: candidate_path.map(|p| original_arg(args_os, &p)),
+        possible_alternates_path: possible_alternates_path.map(|p| original_arg(args_os, &p)),
+        delimiter: delimiter(null),
+        config,
+    }
+}
+
+fn parse_batch_args_or_exit(args_os: &[OsString]) -> BatchOptions {
+    let mut batch = false;
+    let mut paths: Vec<String> = vec![];
+    let mut possible_alternates_path: Option<String> = None;
+    let mut null = false;
+    let mut format = Format::default();
+    let mut config = ConfigOptions::default();
+
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Find the alternates of many paths, gathering the possible alternates once for all of them",
+        );
+        ap.refer(&mut batch).add_option(
+            &["--batch"],
+            StoreTrue,
+            "find the alternates of each PATH, or of each path read from stdin when there are none",
+        );
+        add_config_options(&mut ap, &mut config);
+        ap.refer(&mut possible_alternates_path).add_option(
             &["-f", "--file"],
             StoreOption,
             "possible alternates file, - for stdin",
         );
-        ap.refer(&mut options.include_hidden).add_option(
-            &["-a"],
+        add_null_option(&mut ap, &mut null);
+        ap.refer(&mut format)
+            .add_option(
+                &["--format"],
+                Store,
+                "output format, plain, json, jsonl, fzf, fzf-preview, quickfix, emacs or vscode, plain groups the alternates under a header per path and json outputs an object keyed by path (default: plain)",
+            )
+            .add_option(&["--fzf"], StoreConst(Format::Fzf), "same as --format fzf");
+        ap.refer(&mut paths).add_argument(
+            "PATH",
+            Collect,
+            "paths to find alternates for (default: read from stdin, like the possible alternates file)",
+        );
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(lossy_args(args_os), &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
+    }
+
+    BatchOptions {
+        paths: paths.iter().map(|p| original_arg(args_os, p)).collect(),
+        possible_alternates_path: possible_alternates_path.map(|p| original_arg(args_os, &p)),
+        delimiter: delimiter(null),
+        format,
+        config,
+    }
+}
+
+fn parse_changed_args_or_exit(args_os: &[OsString]) -> ChangedOptions {
+    let mut changed = false;
+    let mut staged = false;
+    let mut since: Option<String> = None;
+    let mut possible_alternates_path: Option<String> = None;
+    let mut null = false;
+    let mut config = ConfigOptions::default();
+
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Output the best alternate of each file changed according to git, each alternate once",
+        );
+        ap.refer(&mut changed).add_option(
+            &["--changed"],
             StoreTrue,
-            "include directory entries whose names begin with a dot",
+            "find the best alternates of the files changed in the working tree, staged or not, against HEAD",
         );
-        ap.refer(&mut options.use_threads).add_option(
-            &["-j"],
+        ap.refer(&mut staged).add_option(
+            &["--staged"],
             StoreTrue,
-            "Use threads to do similarity scoring in parallel (default: false)",
+            "only the files with staged changes",
         );
-        ap.refer(&mut options.path)
-            .add_argument("PATH", Store, "path to find alternate for")
+        ap.refer(&mut since).add_option(
+            &["--since"],
+            StoreOption,
+            "the files changed by the commits in REV..HEAD rather than in the working tree",
+        );
+        add_config_options(&mut ap, &mut config);
+        ap.refer(&mut possible_alternates_path).add_option(
+            &["-f", "--file"],
+            StoreOption,
+            "possible alternates file, - for stdin",
+        );
+        add_null_option(&mut ap, &mut null);
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(lossy_args(args_os), &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
+    }
+
+    let changes = match (staged, since) {
+        (false, None) => Changes::WorkingTree,
+        (true, None) => Changes::Staged,
+        (false, Some(rev)) => Changes::Since(rev),
+        (true, Some(_)) => {
+            printerr!("--staged and --since can not be used together");
+            std::process::exit(2);
+        }
+    };
+
+    ChangedOptions {
+        changes,
+        possible_alternates_path: possible_alternates_path.map(|p| original_arg(args_os, &p)),
+        delimiter: delimiter(null),
+        config,
+    }
+}
+
+fn parse_config_args_or_exit(args: Vec<String>) -> (String, ConfigOptions) {
+    let mut action = String::new();
+    let mut config = ConfigOptions::default();
+
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Inspect the effective configuration");
+        add_config_options(&mut ap, &mut config);
+        ap.refer(&mut action)
+            .add_argument("ACTION", Store, "show - print the merged configuration")
             .required();
-        ap.parse_args_or_exit();
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(args, &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
     }
 
-    options
+    (action, config)
 }
 
-fn scored_paths_to_string(scored_paths: &[ScoredPath]) -> String {
-    let matches: Vec<String> = scored_paths
-        .iter()
-        .map(|(_, path)| path.to_string())
-        // .map(|(score, path)| format!("{:?} {}", score, path.to_string()))
-        .collect();
+fn parse_stdio_args_or_exit(args: Vec<String>) -> ConfigOptions {
+    let mut stdio = false;
+    let mut config = ConfigOptions::default();
 
-    if matches.is_empty() {
-        String::new()
-    } else {
-        matches.join("\n")
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Answer JSON-RPC requests for alternates on stdin and stdout, keeping state between them",
+        );
+        ap.refer(&mut stdio).add_option(
+            &["--stdio"],
+            StoreTrue,
+            "speak JSON-RPC 2.0 over stdin and stdout, each message framed by a Content-Length header",
+        );
+        add_config_options(&mut ap, &mut config);
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(args, &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
     }
+
+    config
 }
 
-fn main() {
-    let options = parse_args_or_exit();
+fn parse_lsp_args_or_exit(args: Vec<String>) -> ConfigOptions {
+    let mut config = ConfigOptions::default();
 
-    let cleansed_path = cleanse_path(&options.path);
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Serve alternates to editors as a language server over stdin and stdout, as code lenses, document links and commands",
+        );
+        add_config_options(&mut ap, &mut config);
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(args, &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
+    }
 
-    let altenate_paths_string = if let Some(unwrapped_file) = options.possible_alternates_path {
-        if unwrapped_file == "-" {
-            let stdin = std::io::stdin();
-            let paths: Vec<String> = stdin.lock().lines().map(|path| path.unwrap()).collect();
-            let scored_paths: Vec<ScoredPath> = match options.use_threads {
-                true => find_alt_with_threads(
-                    &cleansed_path,
-                    paths,
-                    options.truncate,
-                    options.leading_edge_filename_weight,
-                    options.filename_weight,
-                    options.path_weight,
-                )
-                .expect("Failed to find available parallelism"),
-                false => find_alt(
-                    &cleansed_path,
-                    paths,
-                    options.truncate,
-                    options.leading_edge_filename_weight,
-                    options.filename_weight,
-                    options.path_weight,
-                ),
-            };
-            scored_paths_to_string(&scored_paths)
+    config
+}
+
+fn load_config_or_exit(cwd: &Path, overrides: PartialConfig) -> Config {
+    match Config::load(cwd, overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn session_or_exit(cwd: &Path, overrides: PartialConfig) -> Session {
+    match Session::new(cwd, overrides) {
+        Ok(session) => session,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn current_dir_or_exit() -> PathBuf {
+    match std::env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            printerr!("Failure occurred getting the current directory, {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn read_paths_or_exit(
+    possible_alternates_path: &Path,
+    delimiter: u8,
+) -> Box<dyn Iterator<Item = PathBuf>> {
+    let paths: Box<dyn Iterator<Item = io::Result<PathBuf>>> =
+        if possible_alternates_path == Path::new("-") {
+            Box::new(read_paths(std::io::stdin().lock(), delimiter))
         } else {
-            let f = match File::open(&unwrapped_file) {
+            let f = match File::open(possible_alternates_path) {
                 Ok(file) => file,
                 Err(e) => {
-                    printerr!("Failure occurred opening file {}, {}", &unwrapped_file, e);
+                    printerr!(
+                        "Failure occurred opening file {}, {}",
+                        possible_alternates_path.display(),
+                        e
+                    );
                     std::process::exit(1)
                 }
             };
-            let file = BufReader::new(&f);
-            let paths: Vec<String> = file.lines().map(|path| path.unwrap()).collect();
-            let scored_paths: Vec<ScoredPath> = match options.use_threads {
-                true => find_alt_with_threads(
-                    &cleansed_path,
-                    paths,
-                    options.truncate,
-                    options.leading_edge_filename_weight,
-                    options.filename_weight,
-                    options.path_weight,
-                )
-                .expect("Failed to find available parallelism"),
-                false => find_alt(
-                    &cleansed_path,
-                    paths,
-                    options.truncate,
-                    options.leading_edge_filename_weight,
-                    options.filename_weight,
-                    options.path_weight,
-                ),
+            Box::new(read_paths(BufReader::new(f), delimiter))
+        };
+
+    Box::new(paths.map(|path| match path {
+        Ok(path) => path,
+        Err(e) => {
+            printerr!("Failure occurred reading possible alternates, {}", e);
+            std::process::exit(1)
+        }
+    }))
+}
+
+// Read one path per line, or per NUL terminated record, as raw bytes so that
+// paths which are not valid UTF-8 survive, stripping the line endings like
+// BufRead::lines does. Paths are read as they are needed so they can be
+// scored while still arriving.
+fn read_paths<R: BufRead>(reader: R, delimiter: u8) -> impl Iterator<Item = io::Result<PathBuf>> {
+    reader.split(delimiter).map(move |line| {
+        let mut line = line?;
+        if delimiter == b'\n' && line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(path_from_bytes(line))
+    })
+}
+
+fn config_command(args: Vec<String>) {
+    // present `config` as part of the program name in usage and errors
+    let mut config_args: Vec<String> = vec![format!("{} config", args[0])];
+    config_args.extend(args.into_iter().skip(2));
+
+    let (action, config_options) = parse_config_args_or_exit(config_args);
+    if action != "show" {
+        printerr!("Unknown config action {}, expected show", action);
+        std::process::exit(2);
+    }
+
+    let config = load_config_or_exit(&current_dir_or_exit(), config_options.into_partial_config());
+    println!("{}", config_to_string(&config));
+}
+
+fn threads_or_exit<T>(result: Result<T, FindAltWithThreadsError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn compile_rules_or_exit(config: &Config) -> Vec<Rule> {
+    match config.compile_rules() {
+        Ok(rules) => rules,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Where the possible alternates are gathered from.
+#[derive(Debug, PartialEq)]
+enum Gathering {
+    // the given file, or stdin for -
+    File(PathBuf),
+    // the files git lists, optionally along with the untracked ones
+    Git { untracked: bool },
+    // the index kept at the path, walking the directories that changed with
+    // the number of threads
+    Index(PathBuf, usize),
+    // walking the directories with the number of threads, each walking thread
+    // scoring the files it finds
+    ParallelWalk(usize),
+    Walk,
+}
+
+// The given file is read whatever the configuration, otherwise the files git
+// lists when configured, the index when the cache is on, or a walk. Both the
+// index and the walk walk in parallel when there are threads.
+fn gathering(cwd: &Path, config: &Config, possible_alternates_path: Option<PathBuf>) -> Gathering {
+    if let Some(possible_alternates_path) = possible_alternates_path {
+        return Gathering::File(possible_alternates_path);
+    }
+    match config.source.value {
+        CandidateSource::Walk => {}
+        CandidateSource::Git => return Gathering::Git { untracked: false },
+        CandidateSource::GitUntracked => return Gathering::Git { untracked: true },
+    }
+    let index_path = match config.cache.value {
+        true => index_path(cwd, config.include_hidden.value),
+        false => None,
+    };
+    match (index_path, config.threads.value) {
+        (Some(index_path), threads) => Gathering::Index(index_path, threads),
+        (None, 1) => Gathering::Walk,
+        (None, threads) => Gathering::ParallelWalk(threads),
+    }
+}
+
+fn git_files_or_exit(cwd: &Path, config: &Config, untracked: bool) -> Vec<PathBuf> {
+    match ls_files(cwd, untracked, config.include_hidden.value) {
+        Ok(paths) => paths,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn load_cwd_index(config: &Config, index_path: &Path, threads: usize) -> Index {
+    let ignore_hidden = !config.include_hidden.value;
+    load_index(index_path, Path::new("./"), threads, &|root: &Path| {
+        walk_builder(root, ignore_hidden)
+    })
+}
+
+// Gather the possible alternates, from the given file, from git, from the
+// index or by walking the current directory, and rank them for the cleansed
+// path.
+fn find_alternates_or_exit(
+    cwd: &Path,
+    config: &Config,
+    rules: &[Rule],
+    cleansed_path: &Path,
+    possible_alternates_path: Option<PathBuf>,
+    delimiter: u8,
+) -> Vec<ScoredPath> {
+    let keep = match keep_filter(cwd, config, cleansed_path) {
+        Ok(keep) => keep,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
+        }
+    };
+    let scorer = config.scorer();
+
+    match gathering(cwd, config, possible_alternates_path) {
+        // the paths are read, filtered and scored as a stream, so scoring
+        // starts before the last of them has been read
+        Gathering::File(possible_alternates_path) => {
+            let paths = read_paths_or_exit(&possible_alternates_path, delimiter);
+            let paths = paths.filter(|path| keep(path));
+            threads_or_exit(rank(cleansed_path, paths, config, rules))
+        }
+        Gathering::Git { untracked } => {
+            let paths = git_files_or_exit(cwd, config, untracked);
+            let paths = paths.into_iter().filter(|path| keep(path));
+            threads_or_exit(rank(cleansed_path, paths, config, rules))
+        }
+        // the index has the paths along with their parts, so only the
+        // directories that changed since the last run are walked
+        Gathering::Index(index_path, threads) => {
+            let index = load_cwd_index(config, &index_path, threads);
+            let paths = index.indexed_paths().into_iter().filter(|p| keep(&p.path));
+            threads_or_exit(rank(cleansed_path, paths, config, rules))
+        }
+        Gathering::ParallelWalk(threads) => {
+            let walker = walk_builder(Path::new("./"), !config.include_hidden.value)
+                .threads(threads)
+                .build_parallel();
+            threads_or_exit(find_alt_with_parallel_walk(
+                cleansed_path,
+                walker,
+                &keep,
+                config.truncate.value,
+                rules,
+                &scorer,
+            ))
+        }
+        // the paths are walked, filtered and scored as a stream
+        Gathering::Walk => {
+            let paths = get_possible_files(!config.include_hidden.value).filter(|path| keep(path));
+            threads_or_exit(rank(cleansed_path, paths, config, rules))
+        }
+    }
+}
+
+// Gather the possible alternates once, from git, the index, the given file or
+// by walking the current directory, along with their parts so that they are not
+// split again for every path they are ranked for.
+fn possible_alternates_or_exit(
+    cwd: &Path,
+    config: &Config,
+    possible_alternates_path: Option<PathBuf>,
+    delimiter: u8,
+) -> Vec<IndexedPath> {
+    let paths: Vec<PathBuf> = match gathering(cwd, config, possible_alternates_path) {
+        Gathering::File(possible_alternates_path) => {
+            read_paths_or_exit(&possible_alternates_path, delimiter).collect()
+        }
+        Gathering::Git { untracked } => git_files_or_exit(cwd, config, untracked),
+        Gathering::Index(index_path, threads) => {
+            return load_cwd_index(config, &index_path, threads).indexed_paths()
+        }
+        Gathering::ParallelWalk(_) | Gathering::Walk => {
+            get_possible_files(!config.include_hidden.value).collect()
+        }
+    };
+    paths.into_iter().map(IndexedPath::new).collect()
+}
+
+fn batch_command(args_os: &[OsString]) {
+    let options = parse_batch_args_or_exit(args_os);
+    let reads_stdin = options.possible_alternates_path.as_deref() == Some(Path::new("-"));
+    if options.paths.is_empty() && reads_stdin {
+        printerr!(
+            "--batch reads the paths from stdin when none are given, so with -f - they must be given as arguments"
+        );
+        std::process::exit(2);
+    }
+    let query_paths: Vec<PathBuf> = match options.paths.is_empty() {
+        true => read_paths_or_exit(Path::new("-"), options.delimiter)
+            .filter(|path| !path.as_os_str().is_empty())
+            .collect(),
+        false => options.paths,
+    };
+    let cwd = current_dir_or_exit();
+    let config = load_config_or_exit(&cwd, options.config.into_partial_config());
+    let rules = compile_rules_or_exit(&config);
+    let possible_alternates = possible_alternates_or_exit(
+        &cwd,
+        &config,
+        options.possible_alternates_path,
+        options.delimiter,
+    );
+    let results = rank_each_or_exit(&cwd, &config, &rules, &query_paths, &possible_alternates);
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(e) = write_batch(
+        &mut stdout,
+        options.format,
+        options.delimiter,
+        &config.scorer(),
+        &results,
+    )
+    .and_then(|_| stdout.flush())
+    {
+        printerr!("Failure occurred writing alternates, {}", e);
+        std::process::exit(1)
+    }
+}
+
+// Rank the possible alternates for each of the paths, on a thread pool shared
+// by all of them.
+fn rank_each_or_exit(
+    cwd: &Path,
+    config: &Config,
+    rules: &[Rule],
+    query_paths: &[PathBuf],
+    possible_alternates: &[IndexedPath],
+) -> Vec<(PathBuf, Vec<ScoredPath>)> {
+    let pool = match config.threads.value {
+        1 => None,
+        threads => Some(threads_or_exit(thread_pool(threads))),
+    };
+    query_paths
+        .iter()
+        .map(|path| {
+            let cleansed_path = cleanse_path(path);
+            let keep = match keep_filter(cwd, config, &cleansed_path) {
+                Ok(keep) => keep,
+                Err(e) => {
+                    printerr!("{}", e);
+                    std::process::exit(1)
+                }
             };
-            scored_paths_to_string(&scored_paths)
+            let paths = possible_alternates.iter().filter(|p| keep(&p.path));
+            let scored_paths =
+                threads_or_exit(rank_on(pool.as_ref(), &cleansed_path, paths, config, rules));
+            (cleansed_path, scored_paths)
+        })
+        .collect()
+}
+
+// Find the best alternate of each changed file, outputting each alternate
+// once, e.g. to run the tests of the files changed on a branch.
+fn changed_command(args_os: &[OsString]) {
+    let options = parse_changed_args_or_exit(args_os);
+    let cwd = current_dir_or_exit();
+    let mut config = load_config_or_exit(&cwd, options.config.into_partial_config());
+    config.merge(
+        PartialConfig {
+            truncate: Some(1),
+            ..PartialConfig::default()
+        },
+        Source::CommandLine,
+    );
+    let rules = compile_rules_or_exit(&config);
+    let changed_paths = match diff_files(&cwd, &options.changes) {
+        Ok(paths) => paths,
+        Err(e) => {
+            printerr!("{}", e);
+            std::process::exit(1)
         }
-    } else {
-        let unwrapped_paths: Vec<String> = get_possible_files(!options.include_hidden)
-            .iter()
-            .map(|path| path.to_str().unwrap().to_string())
-            .collect();
-        let scored_paths: Vec<ScoredPath> = match options.use_threads {
-            true => find_alt_with_threads(
-                &cleansed_path,
-                unwrapped_paths,
-                options.truncate,
-                options.leading_edge_filename_weight,
-                options.filename_weight,
-                options.path_weight,
-            )
-            .expect("Failed to find available parallelism"),
-            false => find_alt(
-                &cleansed_path,
-                unwrapped_paths,
-                options.truncate,
-                options.leading_edge_filename_weight,
-                options.filename_weight,
-                options.path_weight,
-            ),
-        };
-        scored_paths_to_string(&scored_paths)
     };
-    print!("{}", altenate_paths_string);
+    let possible_alternates = possible_alternates_or_exit(
+        &cwd,
+        &config,
+        options.possible_alternates_path,
+        options.delimiter,
+    );
+    let results = rank_each_or_exit(&cwd, &config, &rules, &changed_paths, &possible_alternates);
+
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    let best_paths: Vec<ScoredPath> = results
+        .into_iter()
+        .flat_map(|(_, scored_paths)| scored_paths)
+        .filter(|(_, path)| seen.insert(path.clone()))
+        .collect();
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(e) = stdout
+        .write_all(&scored_paths_to_bytes(&best_paths, options.delimiter))
+        .and_then(|_| stdout.flush())
+    {
+        printerr!("Failure occurred writing alternates, {}", e);
+        std::process::exit(1)
+    }
+}
+
+fn explain_command(args_os: &[OsString]) {
+    let options = parse_explain_args_or_exit(args_os);
+    let cwd = current_dir_or_exit();
+    let config = load_config_or_exit(&cwd, options.config.into_partial_config());
+    let rules = compile_rules_or_exit(&config);
+    let scorer = config.scorer();
+    let cleansed_path = cleanse_path(&options.path);
+
+    let ranked = options.candidate_path.is_none();
+    let candidate_paths: Vec<PathBuf> = match options.candidate_path {
+        Some(candidate_path) => vec![cleanse_path(&candidate_path)],
+        None => find_alternates_or_exit(
+            &cwd,
+            &config,
+            &rules,
+            &cleansed_path,
+            options.possible_alternates_path,
+            options.delimiter,
+        )
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect(),
+    };
+
+    let explanations: Vec<String> = candidate_paths
+        .iter()
+        .enumerate()
+        .map(|(index, candidate_path)| {
+            let mut lines: Vec<String> = vec![];
+            if ranked {
+                lines.push(format!("#{} {}", index + 1, candidate_path.display()));
+            }
+            let projecting_rule = cleansed_path
+                .to_str()
+                .and_then(|query_path| projecting_rule(&rules, query_path, candidate_path));
+            if let Some(rule) = projecting_rule {
+                lines.push(format!(
+                    "ranked before scored paths, projected by rule {} => {}",
+                    rule.pattern(),
+                    rule.template()
+                ));
+            }
+            lines.push(explain(&scorer, &cleansed_path, candidate_path).to_string());
+            lines.join("\n")
+        })
+        .collect();
+    println!("{}", explanations.join("\n\n"));
+}
+
+fn alt_command(options: Options) {
+    if options.interactive && options.format != Format::Plain {
+        printerr!("--interactive outputs the chosen path, it can not be used with --format");
+        std::process::exit(2);
+    }
+    let cwd = current_dir_or_exit();
+    let overrides = options.config.into_partial_config();
+    let config = load_config_or_exit(&cwd, overrides.clone());
+    let rules = compile_rules_or_exit(&config);
+    let cleansed_path = cleanse_path(&options.path);
+    let from_daemon = match options.possible_alternates_path {
+        None if config.cache.value && config.source.value == CandidateSource::Walk => {
+            daemon_alternates_or_exit(&cwd, overrides, &cleansed_path)
+        }
+        _ => None,
+    };
+    let scored_paths = match from_daemon {
+        Some(scored_paths) => scored_paths,
+        None => find_alternates_or_exit(
+            &cwd,
+            &config,
+            &rules,
+            &cleansed_path,
+            options.possible_alternates_path,
+            options.delimiter,
+        ),
+    };
+    let scored_paths = match options.interactive {
+        true => pick_or_exit(&cleansed_path, scored_paths),
+        false => scored_paths,
+    };
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(e) = write_alternates(
+        &mut stdout,
+        options.format,
+        options.delimiter,
+        &cleansed_path,
+        &config.scorer(),
+        &scored_paths,
+    )
+    .and_then(|_| stdout.flush())
+    {
+        printerr!("Failure occurred writing alternates, {}", e);
+        std::process::exit(1)
+    }
+}
+
+// Let the user choose among the ranked alternates, returning the chosen one
+// to be output, or opening it in their editor and exiting with its status.
+fn pick_or_exit(cleansed_path: &Path, scored_paths: Vec<ScoredPath>) -> Vec<ScoredPath> {
+    if scored_paths.is_empty() {
+        printerr!("No alternates found for {}", cleansed_path.display());
+        std::process::exit(1)
+    }
+    match pick(scored_paths.clone()) {
+        Ok(Some(Choice::Print(path))) => scored_paths
+            .into_iter()
+            .filter(|(_, scored_path)| *scored_path == path)
+            .collect(),
+        Ok(Some(Choice::Edit(path))) => std::process::exit(edit_or_exit(&path)),
+        // cancelled, like an interrupted fzf
+        Ok(None) => std::process::exit(130),
+        Err(e) => {
+            printerr!("Failure occurred picking an alternate, {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Open the path in $VISUAL or $EDITOR, which may include arguments, e.g.
+// `code --wait`, returning the editor's exit code.
+fn edit_or_exit(path: &Path) -> i32 {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|editor| editor.to_string_lossy().into_owned())
+        .find(|editor| !editor.trim().is_empty());
+    let editor = match editor {
+        Some(editor) => editor,
+        None => {
+            printerr!(
+                "Neither $VISUAL nor $EDITOR is set to open {}",
+                path.display()
+            );
+            std::process::exit(1)
+        }
+    };
+    let mut words = editor.split_whitespace();
+    let program = words.next().expect("editor is not blank");
+    match Command::new(program).args(words).arg(path).status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            printerr!("Failure occurred running {}, {}", editor, e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Ask the daemon for the current directory, when one is running, to rank the
+// alternates so that the directories do not have to be walked. Any failure,
+// e.g. a daemon of another version rejecting the request, falls back to
+// finding them without it.
+#[cfg(unix)]
+fn daemon_alternates_or_exit(
+    cwd: &Path,
+    overrides: PartialConfig,
+    cleansed_path: &Path,
+) -> Option<Vec<ScoredPath>> {
+    let alternates_request = Request::Alternates {
+        path: SerializedPath::new(cleansed_path),
+        overrides,
+    };
+    match request(&socket_path(cwd), &alternates_request).ok()? {
+        Response::Alternates(alternates) => Some(
+            alternates
+                .into_iter()
+                .map(|(score, path)| (score, path.to_path_buf()))
+                .collect(),
+        ),
+        Response::Error(_) | Response::Stopped => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn daemon_alternates_or_exit(
+    _cwd: &Path,
+    _overrides: PartialConfig,
+    _cleansed_path: &Path,
+) -> Option<Vec<ScoredPath>> {
+    None
+}
+
+fn parse_daemon_args_or_exit(args: Vec<String>) -> String {
+    let mut action = "start".to_string();
+
+    {
+        // block limits of borrows by refer() method calls
+        let mut ap = ArgumentParser::new();
+        ap.set_description(
+            "Keep the possible alternates of the current directory in memory, updated as files change, and rank them for alt",
+        );
+        ap.refer(&mut action).add_argument(
+            "ACTION",
+            Store,
+            "start - serve alternates until stopped, stop - stop the running daemon (default: start)",
+        );
+        let mut stdout = std::io::stdout();
+        let mut stderr = std::io::stderr();
+        if let Err(code) = ap.parse(args, &mut stdout, &mut stderr) {
+            std::process::exit(code);
+        }
+    }
+
+    action
+}
+
+#[cfg(unix)]
+fn daemon_command(args: Vec<String>) {
+    // present `daemon` as part of the program name in usage and errors
+    let mut daemon_args: Vec<String> = vec![format!("{} daemon", args[0])];
+    daemon_args.extend(args.into_iter().skip(2));
+
+    let action = parse_daemon_args_or_exit(daemon_args);
+    let cwd = current_dir_or_exit();
+    let socket_path = socket_path(&cwd);
+    match action.as_str() {
+        "start" => serve_or_exit(&cwd, &socket_path),
+        "stop" => {
+            if let Err(e) = request(&socket_path, &Request::Stop) {
+                printerr!(
+                    "Failure occurred stopping the daemon for {}, {}",
+                    cwd.display(),
+                    e
+                );
+                std::process::exit(1)
+            }
+        }
+        _ => {
+            printerr!("Unknown daemon action {}, expected start or stop", action);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn daemon_command(_args: Vec<String>) {
+    printerr!("alt daemon is only supported on Unix");
+    std::process::exit(1)
+}
+
+#[cfg(unix)]
+fn serve_or_exit(cwd: &Path, socket_path: &Path) {
+    let listener = match bind(socket_path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            printerr!(
+                "Failure occurred listening on {}, {}",
+                socket_path.display(),
+                e
+            );
+            std::process::exit(1)
+        }
+    };
+    printerr!(
+        "Serving alternates for {} on {}",
+        cwd.display(),
+        socket_path.display()
+    );
+
+    let mut session = session_or_exit(cwd, PartialConfig::default());
+    let result = serve(&listener, |cleansed_path, overrides| {
+        // the config files are read for every request, as alt run without
+        // the daemon would
+        session.reload().map_err(|e| e.to_string())?;
+        let config = session.config(overrides);
+        session
+            .alternates(&config, cleansed_path)
+            .map_err(|e| e.to_string())
+    });
+    let _ = fs::remove_file(socket_path);
+    if let Err(e) = result {
+        printerr!("Failure occurred serving alternates, {}", e);
+        std::process::exit(1)
+    }
+}
+
+// Serve an editor, answering its requests until it closes stdin. The options
+// given on the command line apply to every request.
+fn stdio_command(args: Vec<String>) {
+    let config_options = parse_stdio_args_or_exit(args);
+    let mut session = session_or_exit(&current_dir_or_exit(), config_options.into_partial_config());
+
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    if let Err(e) = rpc::serve(&mut stdin.lock(), &mut stdout.lock(), &mut session) {
+        printerr!("Failure occurred serving requests, {}", e);
+        std::process::exit(1)
+    }
+}
+
+// Serve an editor as a language server, for the root of its workspace, which
+// becomes the current directory so alternates are found relative to it.
+fn lsp_command(args: Vec<String>) {
+    // present `lsp` as part of the program name in usage and errors
+    let mut lsp_args: Vec<String> = vec![format!("{} lsp", args[0])];
+    lsp_args.extend(args.into_iter().skip(2));
+
+    let overrides = parse_lsp_args_or_exit(lsp_args).into_partial_config();
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let result = lsp::serve(&mut stdin.lock(), &mut stdout.lock(), |root| {
+        if let Some(root) = root {
+            std::env::set_current_dir(&root)
+                .map_err(|e| format!("Failure occurred changing to {}, {}", root.display(), e))?;
+        }
+        let cwd = std::env::current_dir()
+            .map_err(|e| format!("Failure occurred getting the current directory, {}", e))?;
+        Session::new(&cwd, overrides).map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(true) => {}
+        // exiting without being asked to shut down first is an error
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            printerr!("Failure occurred serving the language server client, {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Whether the flag, e.g. --stdio, was given as an option, rather than after
+// -- as PATH.
+fn has_flag(args_os: &[OsString], flag: &str) -> bool {
+    args_os
+        .iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == flag)
+}
+
+fn main() {
+    let args_os: Vec<OsString> = std::env::args_os().collect();
+
+    match args_os.get(1).and_then(|arg| arg.to_str()) {
+        Some("config") => config_command(lossy_args(&args_os)),
+        Some("daemon") => daemon_command(lossy_args(&args_os)),
+        Some("explain") => explain_command(&args_os),
+        Some("lsp") => lsp_command(lossy_args(&args_os)),
+        _ if has_flag(&args_os, "--stdio") => stdio_command(lossy_args(&args_os)),
+        _ if has_flag(&args_os, "--batch") => batch_command(&args_os),
+        _ if has_flag(&args_os, "--changed") => changed_command(&args_os),
+        _ => alt_command(parse_args_or_exit(&args_os)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{scored_paths_to_string, ScoredPath};
+    extern crate tempfile;
+
+    use super::{gathering, read_paths, write_alternates, Format, Gathering, ScoredPath};
+    use alt::config::{Config, PartialConfig, Source};
+    use alt::index::index_path;
+    use alt::path::scoring::SimilarityScorer;
+    use std::fs;
+    use std::io;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn read_paths_strips_line_endings() {
+        let paths: Vec<PathBuf> = read_paths(&b"foo/bar.rs\r\nfoo/baz.rs\n"[..], b'\n')
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("foo/bar.rs"), PathBuf::from("foo/baz.rs")]
+        );
+    }
+
+    #[test]
+    fn read_paths_separated_by_nul_may_contain_newlines() {
+        let paths: Vec<PathBuf> = read_paths(&b"foo/bar\r\n.rs\0foo/baz.rs\0"[..], b'\0')
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("foo/bar\r\n.rs"), PathBuf::from("foo/baz.rs")]
+        );
+    }
+
+    #[test]
+    fn read_paths_yields_paths_before_the_input_ends() {
+        let input = (&b"foo/bar.rs\n"[..]).chain(FailingReader);
+        let mut paths = read_paths(io::BufReader::new(input), b'\n');
+
+        assert_eq!(paths.next().unwrap().unwrap(), PathBuf::from("foo/bar.rs"));
+        assert!(paths.next().unwrap().is_err());
+    }
+
+    // A reader that fails, standing in for input that has not arrived yet.
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("not yet"))
+        }
+    }
 
     #[test]
-    fn scored_paths_to_string_with_no_scored_paths() {
-        let scored_paths: Vec<ScoredPath> = Vec::new();
-        let val = scored_paths_to_string(&scored_paths);
+    #[cfg(unix)]
+    fn paths_that_are_not_utf8_are_read_and_output_byte_for_byte() {
+        let input: &[u8] = b"app/caf\xe9.rb\nspec/caf\xe9_spec.rb";
+        let scored_paths: Vec<ScoredPath> = read_paths(input, b'\n')
+            .map(|path| (1.0, path.unwrap()))
+            .collect();
+
+        let mut output: Vec<u8> = vec![];
+        write_alternates(
+            &mut output,
+            Format::Plain,
+            b'\n',
+            Path::new("lib/caf\u{e9}.rb"),
+            &SimilarityScorer::default(),
+            &scored_paths,
+        )
+        .unwrap();
 
-        assert_eq!(val, "");
+        assert_eq!(output, input);
+    }
+
+    fn config(overrides: PartialConfig) -> Config {
+        let mut config = Config::default();
+        config.merge(overrides, Source::CommandLine);
+        config
     }
 
     #[test]
-    fn scored_paths_to_string_with_some_scored_paths() {
-        let scored_paths: Vec<ScoredPath> = vec![
-            (0.3, "some/path/to/a/file.ts".to_owned()),
-            (0.2, "another/path/to/a/foo.ts".to_owned()),
-            (0.1, "foo/bar/car/zar.ts".to_owned()),
-        ];
-        let val = scored_paths_to_string(&scored_paths);
+    fn gathering_with_threads_walks_in_parallel_whether_or_not_the_cache_is_on() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        let threads = PartialConfig {
+            threads: Some(4),
+            ..PartialConfig::default()
+        };
+
+        assert_eq!(
+            gathering(dir.path(), &config(threads.clone()), None),
+            Gathering::Index(index_path(dir.path(), false).unwrap(), 4)
+        );
+        let no_cache = PartialConfig {
+            cache: Some(false),
+            ..threads
+        };
+        assert_eq!(
+            gathering(dir.path(), &config(no_cache), None),
+            Gathering::ParallelWalk(4)
+        );
+    }
+
+    #[test]
+    fn gathering_reads_the_given_file_whatever_the_configuration() {
+        let dir = tempfile::tempdir().unwrap();
+        let threads = PartialConfig {
+            threads: Some(4),
+            ..PartialConfig::default()
+        };
 
         assert_eq!(
-            val,
-            "some/path/to/a/file.ts\nanother/path/to/a/foo.ts\nfoo/bar/car/zar.ts"
+            gathering(dir.path(), &config(threads), Some("-".into())),
+            Gathering::File("-".into())
         );
     }
 }